@@ -1,5 +1,3 @@
-use crate::constant::BASE_URL;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -12,74 +10,37 @@ pub struct Account {
 pub struct AddRes {
     pub errcode: i32,
     pub errmsg: String,
+    #[serde(default)]
     pub open_kfid: String,
 }
 
-fn format_url(path: &str) -> String {
-    format!("{BASE_URL}/account/{path}")
-}
-
-/// 添加客服账号，并可设置客服名称和头像。目前一家企业最多可添加5000个客服账号。
-pub async fn add(token: &str, account: &Account) -> Result<AddRes, reqwest::Error> {
-    let client = Client::new();
-    let path = format!("add?access_token={token}");
-    let url = format_url(&path);
-    client.post(url).json(account).send().await?.json().await
-}
-
 #[derive(Debug, Serialize)]
-struct DelReq {
-    kf_id: String,
+pub struct DelReq {
+    pub open_kfid: String,
 }
 
 impl DelReq {
-    fn new(kf_id: &str) -> Self {
+    pub fn new(open_kfid: &str) -> Self {
         Self {
-            kf_id: kf_id.to_string(),
+            open_kfid: open_kfid.to_string(),
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SimpleRes {
-    pub errocde: i32,
+    pub errcode: i32,
     pub errmsg: String,
 }
 
-/// 删除客服账号
-pub async fn del(token: &str, kf_id: &str) -> Result<SimpleRes, reqwest::Error> {
-    let client = Client::new();
-    let path = format!("del?access_token={token}");
-    let url = format_url(&path);
-    let del_req = DelReq::new(kf_id);
-    client
-        .post(url)
-        .json::<DelReq>(&del_req)
-        .send()
-        .await?
-        .json()
-        .await
-}
-
+/// 修改客服账号，未指定的字段保持不变
 #[derive(Debug, Serialize)]
 pub struct UpdateAccount {
-    pub name: String,
-    pub open_kf_id: String,
-    pub media_id: String,
-}
-
-/// 修改已有的客服账号，可修改客服名称和头像。
-pub async fn update(token: &str, account: UpdateAccount) -> Result<SimpleRes, reqwest::Error> {
-    let client = Client::new();
-    let path = format!("update?access_token={token}");
-    let url = format_url(&path);
-    client
-        .post(url)
-        .json::<UpdateAccount>(&account)
-        .send()
-        .await?
-        .json()
-        .await
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub open_kfid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -99,17 +60,10 @@ pub struct ListItemRes {
 pub struct ListRes {
     pub errcode: i32,
     pub errmsg: String,
+    #[serde(default)]
     pub account_list: Vec<ListItemRes>,
 }
 
-/// 获取客服账号列表，包括所有的客服账号的客服ID、名称和头像
-pub async fn list(token: &str, page: &Page) -> Result<ListRes, reqwest::Error> {
-    let client = Client::new();
-    let path = format!("list?access_token={token}");
-    let url = format_url(&path);
-    client.post(url).json(page).send().await?.json().await
-}
-
 #[derive(Debug, Serialize)]
 pub struct AccountLinkReq {
     pub open_kfid: String,
@@ -120,13 +74,130 @@ pub struct AccountLinkReq {
 pub struct AccountLinkRes {
     pub errcode: i32,
     pub errmsg: String,
+    #[serde(default)]
     pub url: String,
 }
 
-/// 企业可通过此接口获取带有不同参数的客服链接，不同客服账号对应不同的客服链接。获取后，企业可将链接嵌入到网页等场景中，微信用户点击链接即可向对应的客服账号发起咨询。企业可依据参数来识别用户的咨询来源等。
-pub async fn link(token: &str, account: &AccountLinkReq) -> Result<AccountLinkRes, reqwest::Error> {
-    let client = Client::new();
-    let path = format!("add_contact_way?access_token={token}");
-    let url = format_url(&path);
-    client.post(url).json(account).send().await?.json().await
+json_endpoint!(
+    Account,
+    "/kf/account/add",
+    AddRes,
+    idempotent = |_account| false
+);
+json_endpoint!(
+    DelReq,
+    "/kf/account/del",
+    SimpleRes,
+    open_kfid = |req| Some(req.open_kfid.as_str())
+);
+json_endpoint!(
+    UpdateAccount,
+    "/kf/account/update",
+    SimpleRes,
+    open_kfid = |account| Some(account.open_kfid.as_str())
+);
+json_endpoint!(Page, "/kf/account/list", ListRes);
+json_endpoint!(
+    AccountLinkReq,
+    "/kf/add_contact_way",
+    AccountLinkRes,
+    open_kfid = |req| Some(req.open_kfid.as_str())
+);
+
+api! {
+    /// 添加客服账号，并可设置客服名称和头像。目前一家企业最多可添加5000个客服账号。
+    fn add(token: &str, account: &Account) -> AddRes = account;
+    /// 删除客服账号
+    fn del(token: &str, open_kfid: &str) -> SimpleRes = DelReq::new(open_kfid);
+    /// 修改已有的客服账号，可修改客服名称和头像。
+    fn update(token: &str, account: UpdateAccount) -> SimpleRes = account;
+    /// 获取客服账号列表，包括所有的客服账号的客服ID、名称和头像
+    fn list(token: &str, page: &Page) -> ListRes = page;
+    /// 企业可通过此接口获取带有不同参数的客服链接，不同客服账号对应不同的客服链接。获取后，企业可将链接嵌入到网页等场景中，微信用户点击链接即可向对应的客服账号发起咨询。企业可依据参数来识别用户的咨询来源等。
+    fn link(token: &str, account: &AccountLinkReq) -> AccountLinkRes = account;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::Endpoint;
+    use crate::transport::Method;
+    use serde_json::{from_slice, json, Value};
+
+    #[test]
+    fn test_del_endpoint() {
+        let req =
+            DelReq::new("OPEN_KFID").to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(req.method, Method::Post);
+        assert_eq!(
+            req.url,
+            "https://qyapi.weixin.qq.com/cgi-bin/kf/account/del?access_token=TOKEN"
+        );
+        let body: Value = from_slice(&req.body).unwrap();
+        assert_eq!(body, json!({"open_kfid": "OPEN_KFID"}));
+    }
+
+    #[test]
+    fn test_update_endpoint() {
+        let account = UpdateAccount {
+            name: Some("NAME".to_string()),
+            open_kfid: "OPEN_KFID".to_string(),
+            media_id: Some("MEDIA_ID".to_string()),
+        };
+        let body: Value = from_slice(&account.body().unwrap()).unwrap();
+        let expected = json!({"name": "NAME", "open_kfid": "OPEN_KFID", "media_id": "MEDIA_ID"});
+        assert_eq!(body, expected);
+
+        let account = UpdateAccount {
+            name: None,
+            open_kfid: "OPEN_KFID".to_string(),
+            media_id: Some("MEDIA_ID".to_string()),
+        };
+        let body: Value = from_slice(&account.body().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({"open_kfid": "OPEN_KFID", "media_id": "MEDIA_ID"})
+        );
+    }
+
+    #[test]
+    fn test_link_endpoint() {
+        let req = AccountLinkReq {
+            open_kfid: "OPEN_KFID".to_string(),
+            scene: "12345".to_string(),
+        }
+        .to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(
+            req.url,
+            "https://qyapi.weixin.qq.com/cgi-bin/kf/add_contact_way?access_token=TOKEN"
+        );
+        let body: Value = from_slice(&req.body).unwrap();
+        assert_eq!(body, json!({"open_kfid": "OPEN_KFID", "scene": "12345"}));
+    }
+
+    #[test]
+    fn test_simple_response() {
+        let res =
+            DelReq::parse_response(br#"{"errcode": 40014, "errmsg": "invalid access_token"}"#)
+                .unwrap();
+        assert_eq!(res.errcode, 40014);
+        assert!(DelReq::parse_response(br#"{"errocde": 0, "errmsg": "ok"}"#).is_err());
+    }
+
+    #[test]
+    fn test_list_response() {
+        let bytes = br#"{
+            "errcode": 0,
+            "errmsg": "ok",
+            "account_list": [
+                {"open_kfid": "OPEN_KFID", "name": "NAME", "avatar": "AVATAR"}
+            ]
+        }"#;
+        let res = Page::parse_response(bytes).unwrap();
+        assert_eq!(res.account_list[0].open_kfid, "OPEN_KFID");
+
+        let res = Page::parse_response(br#"{"errcode": 40014, "errmsg": "invalid access_token"}"#)
+            .unwrap();
+        assert_eq!(res.errcode, 40014);
+    }
 }
@@ -0,0 +1,161 @@
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Bytes, Data, Query};
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Resource, ResponseError};
+
+use crate::callback::CallbackConfig;
+use crate::parse::WeiXinCallbackRes;
+use crate::verify::{VerifyErr, WeiXinCallbackParam};
+
+/// 回调处理错误类型，响应时只返回状态码，不包含错误详情
+#[derive(Debug)]
+pub enum CallbackErr {
+    /// 未通过`app_data`注册[`CallbackConfig`]
+    MissingConfig,
+    /// URL参数不完整
+    Query,
+    /// 请求体无法读取或不是UTF-8
+    Body,
+    Verify(VerifyErr),
+}
+
+impl From<VerifyErr> for CallbackErr {
+    fn from(value: VerifyErr) -> Self {
+        CallbackErr::Verify(value)
+    }
+}
+
+impl Display for CallbackErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackErr::MissingConfig => write!(f, "callback config is not registered"),
+            CallbackErr::Query => write!(f, "invalid callback query"),
+            CallbackErr::Body => write!(f, "invalid callback body"),
+            CallbackErr::Verify(VerifyErr::Signature) => write!(f, "invalid signature"),
+            CallbackErr::Verify(VerifyErr::CorpId(_)) => write!(f, "corp id mismatch"),
+            CallbackErr::Verify(_) => write!(f, "invalid callback message"),
+        }
+    }
+}
+
+impl ResponseError for CallbackErr {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CallbackErr::MissingConfig => StatusCode::INTERNAL_SERVER_ERROR,
+            CallbackErr::Verify(err) => {
+                StatusCode::from_u16(err.status()).unwrap_or(StatusCode::BAD_REQUEST)
+            }
+            CallbackErr::Query | CallbackErr::Body => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::new(self.status_code())
+    }
+}
+
+fn config(req: &HttpRequest) -> Result<Data<CallbackConfig>, CallbackErr> {
+    req.app_data::<Data<CallbackConfig>>()
+        .cloned()
+        .ok_or(CallbackErr::MissingConfig)
+}
+
+fn params(req: &HttpRequest) -> Result<WeiXinCallbackParam, CallbackErr> {
+    Query::<WeiXinCallbackParam>::from_query(req.query_string())
+        .map(Query::into_inner)
+        .map_err(|_| CallbackErr::Query)
+}
+
+/// 校验并解密后的回调事件，需要通过`app_data`注册`Data<CallbackConfig>`
+#[derive(Debug, Clone)]
+pub struct CallbackEvent(pub WeiXinCallbackRes);
+
+impl FromRequest for CallbackEvent {
+    type Error = CallbackErr;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let mut payload = payload.take();
+        Box::pin(async move {
+            let config = config(&req)?;
+            let params = params(&req)?;
+            let body = Bytes::from_request(&req, &mut payload)
+                .await
+                .map_err(|_| CallbackErr::Body)?;
+            let body = std::str::from_utf8(&body).map_err(|_| CallbackErr::Body)?;
+            let event = config.decrypt(&params, body)?;
+            Ok(CallbackEvent(event))
+        })
+    }
+}
+
+/// 验证URL有效性，响应echostr明文
+pub async fn verify(req: HttpRequest) -> Result<String, CallbackErr> {
+    let config = config(&req)?;
+    let params = params(&req)?;
+    let msg = config.verify(&params)?;
+    Ok(msg)
+}
+
+/// 处理GET验证请求的服务，可继续添加处理POST回调的路由
+pub fn verify_service(path: &str) -> Resource {
+    web::resource(path).route(web::get().to(verify))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callback::SUCCESS;
+    use actix_web::{test, App};
+
+    const ENCODING_AES_KEY: &str = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+
+    async fn receive(event: CallbackEvent) -> &'static str {
+        assert_eq!(event.0.open_kf_id, "OPEN_KFID");
+        SUCCESS
+    }
+
+    #[actix_web::test]
+    async fn test_callback() {
+        let config = CallbackConfig::new("TOKEN", ENCODING_AES_KEY, "CORP_ID");
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(config.clone()))
+                .service(verify_service("/callback").route(web::post().to(receive))),
+        )
+        .await;
+        let event = WeiXinCallbackRes::new("CORP_ID", "SYNC_TOKEN", "OPEN_KFID");
+        let (body, params) = config
+            .encrypt("CORP_ID", &event.to_xml(), "1409659813", "1372623149")
+            .unwrap();
+        let uri = format!(
+            "/callback?msg_signature={}&timestamp={}&nonce={}",
+            params.msg_signature, params.timestamp, params.nonce
+        );
+
+        let req = test::TestRequest::post()
+            .uri(&uri)
+            .set_payload(body.clone())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, SUCCESS);
+
+        let req = test::TestRequest::post()
+            .uri("/callback?msg_signature=0&timestamp=1&nonce=1")
+            .set_payload(body)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert!(test::read_body(res).await.is_empty());
+
+        let req = test::TestRequest::get().uri("/callback").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use ::axum::extract::{Query, State};
+use ::axum::http::StatusCode;
+use ::axum::routing::get;
+use ::axum::Router;
+
+use crate::callback::{CallbackConfig, EventSink, SUCCESS};
+use crate::verify::{VerifyErr, WeiXinCallbackParam};
+
+struct CallbackState<S> {
+    config: CallbackConfig,
+    sink: S,
+}
+
+/// 生成处理回调的路由，GET请求用于验证URL，POST请求用于接收回调
+///
+/// 解析后的回调事件交给`sink`，随后立即响应`success`，
+/// 拉取消息等耗时的处理应由`sink`的接收方异步进行。
+pub fn router<S: EventSink>(config: CallbackConfig, sink: S) -> Router {
+    let state = Arc::new(CallbackState { config, sink });
+    Router::new()
+        .route("/", get(verify::<S>).post(receive::<S>))
+        .with_state(state)
+}
+
+fn status(err: &VerifyErr) -> StatusCode {
+    StatusCode::from_u16(err.status()).unwrap_or(StatusCode::BAD_REQUEST)
+}
+
+async fn verify<S: EventSink>(
+    State(state): State<Arc<CallbackState<S>>>,
+    Query(params): Query<WeiXinCallbackParam>,
+) -> Result<String, StatusCode> {
+    state.config.verify(&params).map_err(|e| status(&e))
+}
+
+async fn receive<S: EventSink>(
+    State(state): State<Arc<CallbackState<S>>>,
+    Query(params): Query<WeiXinCallbackParam>,
+    body: String,
+) -> Result<&'static str, StatusCode> {
+    let event = state
+        .config
+        .decrypt(&params, &body)
+        .map_err(|e| status(&e))?;
+    if state.sink.push(event) {
+        Ok(SUCCESS)
+    } else {
+        Err(StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::WeiXinCallbackRes;
+    use ::axum::body::{to_bytes, Body};
+    use ::axum::http::Request;
+    use tokio::sync::mpsc;
+    use tower::ServiceExt;
+
+    const ENCODING_AES_KEY: &str = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+
+    fn query(params: &WeiXinCallbackParam) -> String {
+        let query = serde_urlencoded::to_string([
+            ("msg_signature", &params.msg_signature),
+            ("timestamp", &params.timestamp),
+            ("nonce", &params.nonce),
+            ("echostr", &params.echostr),
+        ])
+        .unwrap();
+        format!("/?{query}")
+    }
+
+    async fn body_string(body: Body) -> String {
+        let bytes = to_bytes(body, usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let config = CallbackConfig::new("QDG6eK", ENCODING_AES_KEY, "wx5823bf96d3bd56c7");
+        let (sender, _) = mpsc::channel(1);
+        let app = router(config, sender);
+        let params = WeiXinCallbackParam {
+            timestamp: "1409659813".to_string(),
+            nonce: "1372623149".to_string(),
+            echostr: "RypEvHKD8QQKFhvQ6QleEB4J58tiPdvo+rtK1I9qca6aM/wvqnLSV5zEPeusUiX5L5X/0lWfrf0QADHHhGd3QczcdCUpj911L3vg3W/sYYvuJTs3TUUkSUXxaccAS0qhxchrRYt66wiSpGLYL42aM6A8dTT+6k4aSknmPj48kzJs8qLjvd4Xgpue06DOdnLxAUHzM6+kDZ+HMZfJYuR+LtwGc2hgf5gsijff0ekUNXZiqATP7PF5mZxZ3Izoun1s4zG4LUMnvw2r+KqCKIw+3IQH03v+BCA9nMELNqbSf6tiWSrXJB3LAVGUcallcrw8V2t9EL4EhzJWrQUax5wLVMNS0+rUPA3k22Ncx4XXZS9o0MBH27Bo6BpNelZpS+/uh9KsNlY6bHCmJU9p8g7m3fVKn28H3KDYA5Pl/T8Z1ptDAVe0lXdQ2YoyyH2uyPIGHBZZIs2pDBS8R07+qN+E7Q==".to_string(),
+            msg_signature: "477715d11cdb4164915debcba66cb864d751f3e6".to_string(),
+        };
+        let req = Request::get(query(&params)).body(Body::empty()).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(body_string(res.into_body()).await.starts_with("<xml>"));
+
+        let params = WeiXinCallbackParam {
+            msg_signature: "0".repeat(40),
+            ..params
+        };
+        let req = Request::get(query(&params)).body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_receive() {
+        let config = CallbackConfig::new("TOKEN", ENCODING_AES_KEY, "CORP_ID");
+        let (sender, mut receiver) = mpsc::channel(1);
+        let app = router(config.clone(), sender);
+        let event = WeiXinCallbackRes::new("CORP_ID", "SYNC_TOKEN", "OPEN_KFID");
+        let (body, params) = config
+            .encrypt("CORP_ID", &event.to_xml(), "1409659813", "1372623149")
+            .unwrap();
+
+        let req = Request::post(query(&params))
+            .body(Body::from(body.clone()))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body_string(res.into_body()).await, SUCCESS);
+        assert_eq!(receiver.recv().await.unwrap(), event);
+
+        let req = Request::post(query(&params))
+            .body(Body::from("<xml></xml>"))
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use kf_wx::secret::Secret;
+use serde::Deserialize;
+
+use crate::CliErr;
+
+/// 配置文件的内容，命令行参数和环境变量优先于配置文件
+///
+/// ```toml
+/// corpid = "ww12345678910"
+/// secret = "SECRET"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub corpid: Option<String>,
+    pub secret: Option<Secret<String>>,
+    pub access_token: Option<Secret<String>>,
+    pub base_url: Option<String>,
+}
+
+/// 默认配置文件`~/.config/kf-wx/config.toml`
+fn default_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(Path::new(&home).join(".config/kf-wx/config.toml"))
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, CliErr> {
+        toml::from_str(text).map_err(|e| CliErr::Config(e.to_string()))
+    }
+
+    /// 读取指定的配置文件，未指定时读取默认配置文件，默认配置文件不存在时返回空配置
+    pub fn load(path: Option<&Path>) -> Result<Self, CliErr> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| CliErr::Config(format!("{}: {e}", path.display())))?;
+        Self::parse(&text)
+    }
+
+    /// 用`other`中设置了的字段覆盖当前配置
+    pub fn merge(self, other: Config) -> Self {
+        Self {
+            corpid: other.corpid.or(self.corpid),
+            secret: other.secret.or(self.secret),
+            access_token: other.access_token.or(self.access_token),
+            base_url: other.base_url.or(self.base_url),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let file = Config::parse("corpid = \"FILE_ID\"\nsecret = \"FILE_SECRET\"").unwrap();
+        let args = Config {
+            secret: Some(Secret::from("ENV_SECRET")),
+            ..Default::default()
+        };
+        let config = file.merge(args);
+        assert_eq!(config.corpid.as_deref(), Some("FILE_ID"));
+        assert_eq!(
+            config.secret.as_ref().map(Secret::expose),
+            Some(&"ENV_SECRET".to_string())
+        );
+        assert!(!format!("{config:?}").contains("SECRET"));
+        assert!(matches!(
+            Config::parse("corp_id = \"ID\""),
+            Err(CliErr::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,845 @@
+//! 管理客服账号、接待人员和消息的命令行工具
+//!
+//! 凭证按命令行参数、环境变量（`KF_WX_CORPID`、`KF_WX_SECRET`、`KF_WX_ACCESS_TOKEN`）、
+//! 配置文件（`--config`或`~/.config/kf-wx/config.toml`）的顺序读取。
+
+mod config;
+mod output;
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use kf_wx::account::{Account, AccountLinkReq, DelReq, Page, UpdateAccount};
+use kf_wx::media::{GetMedia, MediaType, Upload};
+use kf_wx::receive::{MoreMsg, MsgRes, SyncMsg};
+use kf_wx::secret::Secret;
+use kf_wx::send::OutgoingMessage;
+use kf_wx::servicer::{AddServicer, DelServicer, ListServicer, ServicerReq, ServicerRes};
+use kf_wx::{Client, ClientErr, GetToken, Message, MsgItem};
+use serde_json::{json, Value};
+use tokio::time::Instant;
+
+use config::Config;
+use output::{Format, Output};
+
+/// 命令行工具错误类型
+#[derive(Debug)]
+pub enum CliErr {
+    /// 缺少凭证或配置文件格式错误
+    Config(String),
+    /// 请求失败
+    Client(ClientErr),
+    /// 接口返回错误码
+    Api(i32, String),
+    /// 读写文件失败
+    Io(std::io::Error),
+    /// 参数错误
+    Usage(String),
+}
+
+impl Display for CliErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliErr::Config(message) | CliErr::Usage(message) => f.write_str(message),
+            CliErr::Api(errcode, errmsg) => write!(f, "errcode {errcode}: {errmsg}"),
+            CliErr::Client(err) => err.fmt(f),
+            CliErr::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CliErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliErr::Client(err) => Some(err),
+            CliErr::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClientErr> for CliErr {
+    fn from(value: ClientErr) -> Self {
+        CliErr::Client(value)
+    }
+}
+
+impl From<std::io::Error> for CliErr {
+    fn from(value: std::io::Error) -> Self {
+        CliErr::Io(value)
+    }
+}
+
+/// 接口返回非0错误码时转为错误
+fn check(errcode: i32, errmsg: &str) -> Result<(), CliErr> {
+    match errcode {
+        0 => Ok(()),
+        _ => Err(CliErr::Api(errcode, errmsg.to_string())),
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "kf-wx", version, about = "管理企业微信客服账号")]
+struct Cli {
+    /// 配置文件
+    #[arg(long, global = true, env = "KF_WX_CONFIG")]
+    config: Option<PathBuf>,
+    /// 企业ID
+    #[arg(long, global = true, env = "KF_WX_CORPID")]
+    corpid: Option<String>,
+    /// 微信客服的Secret
+    #[arg(long, global = true, env = "KF_WX_SECRET", hide_env_values = true)]
+    secret: Option<Secret<String>>,
+    /// 直接使用的access_token，设置后不再获取
+    #[arg(
+        long,
+        global = true,
+        env = "KF_WX_ACCESS_TOKEN",
+        hide_env_values = true
+    )]
+    access_token: Option<Secret<String>>,
+    /// 接口地址
+    #[arg(long, global = true, env = "KF_WX_BASE_URL")]
+    base_url: Option<String>,
+    /// 输出格式
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 获取access_token
+    Token,
+    /// 管理客服账号
+    #[command(subcommand)]
+    Account(AccountCmd),
+    /// 管理接待人员
+    #[command(subcommand)]
+    Servicer(ServicerCmd),
+    /// 发送消息
+    #[command(subcommand)]
+    Send(SendCmd),
+    /// 拉取消息
+    Sync(SyncArgs),
+    /// 管理临时素材
+    #[command(subcommand)]
+    Media(MediaCmd),
+}
+
+#[derive(Debug, Subcommand)]
+enum AccountCmd {
+    /// 列出客服账号
+    List {
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// 添加客服账号
+    Add {
+        #[arg(long)]
+        name: String,
+        /// 头像的临时素材ID
+        #[arg(long)]
+        media_id: String,
+    },
+    /// 修改客服账号，至少指定名称和头像之一
+    #[command(group(ArgGroup::new("fields").args(["name", "media_id"]).required(true).multiple(true)))]
+    Update {
+        open_kfid: String,
+        #[arg(long)]
+        name: Option<String>,
+        /// 头像的临时素材ID
+        #[arg(long)]
+        media_id: Option<String>,
+    },
+    /// 删除客服账号
+    Del { open_kfid: String },
+    /// 获取客服链接
+    Link {
+        open_kfid: String,
+        /// 场景值，会在进入会话事件中返回
+        #[arg(long, default_value = "")]
+        scene: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ServicerCmd {
+    /// 列出接待人员
+    List { open_kfid: String },
+    /// 添加接待人员
+    Add {
+        open_kfid: String,
+        #[arg(required = true)]
+        userids: Vec<String>,
+    },
+    /// 删除接待人员
+    Del {
+        open_kfid: String,
+        #[arg(required = true)]
+        userids: Vec<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct Target {
+    /// 接收消息的客户
+    #[arg(long)]
+    to: String,
+    /// 发送消息的客服账号
+    #[arg(long)]
+    kf: String,
+}
+
+#[derive(Debug, Subcommand)]
+enum SendCmd {
+    /// 发送文本消息
+    Text {
+        #[command(flatten)]
+        target: Target,
+        content: String,
+    },
+    /// 发送图片消息，指定本地文件时先上传为临时素材
+    Image {
+        #[command(flatten)]
+        target: Target,
+        #[arg(long, required_unless_present = "file", conflicts_with = "file")]
+        media_id: Option<String>,
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct SyncArgs {
+    open_kfid: String,
+    /// 从该游标开始拉取
+    #[arg(long)]
+    cursor: Option<String>,
+    /// 回调事件中的token
+    #[arg(long)]
+    token: Option<String>,
+    /// 每次拉取的条数
+    #[arg(long, default_value_t = kf_wx::sync::SYNC_LIMIT)]
+    limit: i32,
+    /// 持续拉取新消息，配置了企业ID和Secret时会在access_token到期或失效时重新获取
+    #[arg(long)]
+    follow: bool,
+    /// 持续拉取时没有新消息的等待时间（秒）
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
+}
+
+#[derive(Debug, Subcommand)]
+enum MediaCmd {
+    /// 上传临时素材
+    Upload {
+        file: PathBuf,
+        #[arg(long = "type", value_parser = parse_media_type, default_value = "image")]
+        media_type: MediaType,
+    },
+    /// 下载临时素材，未指定输出文件时写到标准输出
+    Get {
+        media_id: String,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn parse_media_type(value: &str) -> Result<MediaType, String> {
+    serde_json::from_value(json!(value))
+        .map_err(|_| format!("unknown media type `{value}`, expected image, voice, video or file"))
+}
+
+/// access_token过期（42001）和无效（40014）的错误码
+const TOKEN_ERRCODES: [i32; 2] = [42001, 40014];
+/// 在access_token到期前提前刷新的时间
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// 用access_token创建客户端
+type NewClient = Box<dyn Fn(&str) -> Client>;
+
+/// 持有access_token的客户端，配置了企业ID和Secret时在到期前或失效后重新获取
+struct Session {
+    config: Config,
+    new_client: NewClient,
+    client: Client,
+    expires_at: Option<Instant>,
+}
+
+impl Session {
+    /// 根据配置创建客户端，没有access_token时使用企业ID和Secret获取
+    async fn connect(config: Config) -> Result<Self, CliErr> {
+        let base = config.clone();
+        let new_client: NewClient = Box::new(move |token| with_base_url(Client::new(token), &base));
+        Self::with_client(config, new_client).await
+    }
+
+    async fn with_client(config: Config, new_client: NewClient) -> Result<Self, CliErr> {
+        let token = config.access_token.as_ref().map(Secret::expose);
+        let client = new_client(token.map_or("", String::as_str));
+        let mut session = Self {
+            config,
+            new_client,
+            client,
+            expires_at: None,
+        };
+        if session.config.access_token.is_none() {
+            session.refresh().await?;
+        }
+        Ok(session)
+    }
+
+    fn can_refresh(&self) -> bool {
+        self.config.corpid.is_some() && self.config.secret.is_some()
+    }
+
+    /// 重新获取access_token并替换客户端
+    async fn refresh(&mut self) -> Result<(), CliErr> {
+        let (token, expires_in) = fetch_token(&(self.new_client)(""), &self.config).await?;
+        self.client = (self.new_client)(token.expose());
+        let expires_in = Duration::from_secs(expires_in.max(0) as u64);
+        self.expires_at = Some(Instant::now() + expires_in.saturating_sub(TOKEN_REFRESH_MARGIN));
+        Ok(())
+    }
+
+    /// 拉取消息，access_token即将到期时先刷新，失效时刷新后重试一次
+    async fn sync_msg(&mut self, msg: &SyncMsg) -> Result<MsgRes, CliErr> {
+        if self.expires_at.is_some_and(|at| Instant::now() >= at) {
+            self.refresh().await?;
+        }
+        let res = self.client.sync_msg(msg).await?;
+        if TOKEN_ERRCODES.contains(&res.errcode) && self.can_refresh() {
+            self.refresh().await?;
+            return Ok(self.client.sync_msg(msg).await?);
+        }
+        Ok(res)
+    }
+}
+
+/// 根据配置创建客户端
+async fn connect(config: &Config) -> Result<Client, CliErr> {
+    Ok(Session::connect(config.clone()).await?.client)
+}
+
+fn with_base_url(client: Client, config: &Config) -> Client {
+    match &config.base_url {
+        Some(base_url) => client.base_url(base_url),
+        None => client,
+    }
+}
+
+/// 获取access_token和有效期，`client`不需要持有access_token
+async fn fetch_token(client: &Client, config: &Config) -> Result<(Secret<String>, i32), CliErr> {
+    let (Some(corpid), Some(secret)) = (&config.corpid, &config.secret) else {
+        return Err(CliErr::Config(
+            "missing credentials, set KF_WX_CORPID and KF_WX_SECRET or use --config".to_string(),
+        ));
+    };
+    let res = client.call(&GetToken::new(corpid, secret.expose())).await?;
+    check(res.errcode, &res.errmsg)?;
+    Ok((res.access_token, res.expires_in))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "media".to_string())
+}
+
+async fn upload(client: &Client, path: &Path, media_type: MediaType) -> Result<Output, CliErr> {
+    let data = std::fs::read(path)?;
+    let res = client
+        .call(&Upload::new(media_type, &file_name(path), data))
+        .await?;
+    check(res.errcode, &res.errmsg)?;
+    let json =
+        json!({ "type": res.media_type, "media_id": res.media_id, "created_at": res.created_at });
+    Ok(Output::new(json, &["media_id", "type", "created_at"]).row([
+        &res.media_id,
+        &res.media_type,
+        &res.created_at,
+    ]))
+}
+
+fn servicer_output(res: ServicerRes) -> Result<Output, CliErr> {
+    check(res.errcode, &res.errmsg)?;
+    let json: Vec<Value> = res
+        .result_list
+        .iter()
+        .map(|result| json!({ "userid": result.userid, "errcode": result.errcode, "errmsg": result.errmsg }))
+        .collect();
+    let output = Output::new(json!(json), &["userid", "errcode", "errmsg"]);
+    Ok(res.result_list.iter().fold(output, |output, result| {
+        output.row([
+            result.userid.clone(),
+            result.errcode.to_string(),
+            result.errmsg.clone(),
+        ])
+    }))
+}
+
+async fn account(client: &Client, cmd: AccountCmd) -> Result<Output, CliErr> {
+    let output = match cmd {
+        AccountCmd::List { offset, limit } => {
+            let res = client.call(&Page { offset, limit }).await?;
+            check(res.errcode, &res.errmsg)?;
+            let json: Vec<Value> = res
+                .account_list
+                .iter()
+                .map(|account| json!({ "open_kfid": account.open_kfid, "name": account.name, "avatar": account.avatar }))
+                .collect();
+            let output = Output::new(json!(json), &["open_kfid", "name", "avatar"]);
+            res.account_list.iter().fold(output, |output, account| {
+                output.row([&account.open_kfid, &account.name, &account.avatar])
+            })
+        }
+        AccountCmd::Add { name, media_id } => {
+            let res = client.call(&Account { name, media_id }).await?;
+            check(res.errcode, &res.errmsg)?;
+            Output::new(json!({ "open_kfid": res.open_kfid }), &["open_kfid"]).row([res.open_kfid])
+        }
+        AccountCmd::Update {
+            open_kfid,
+            name,
+            media_id,
+        } => {
+            let req = UpdateAccount {
+                name,
+                open_kfid,
+                media_id,
+            };
+            let res = client.call(&req).await?;
+            check(res.errcode, &res.errmsg)?;
+            Output::new(json!({ "errcode": 0 }), &["result"]).row(["ok"])
+        }
+        AccountCmd::Del { open_kfid } => {
+            let res = client.call(&DelReq::new(&open_kfid)).await?;
+            check(res.errcode, &res.errmsg)?;
+            Output::new(json!({ "errcode": 0 }), &["result"]).row(["ok"])
+        }
+        AccountCmd::Link { open_kfid, scene } => {
+            let res = client.call(&AccountLinkReq { open_kfid, scene }).await?;
+            check(res.errcode, &res.errmsg)?;
+            Output::new(json!({ "url": res.url }), &["url"]).row([res.url])
+        }
+    };
+    Ok(output)
+}
+
+async fn servicer(client: &Client, cmd: ServicerCmd) -> Result<Output, CliErr> {
+    match cmd {
+        ServicerCmd::List { open_kfid } => {
+            let res = client.call(&ListServicer::new(&open_kfid)).await?;
+            check(res.errcode, &res.errmsg)?;
+            let json: Vec<Value> = res
+                .servicer_list
+                .iter()
+                .map(|servicer| json!({ "userid": servicer.userid, "status": servicer.status }))
+                .collect();
+            let output = Output::new(json!(json), &["userid", "status"]);
+            Ok(res.servicer_list.iter().fold(output, |output, servicer| {
+                output.row([servicer.userid.clone(), format!("{:?}", servicer.status)])
+            }))
+        }
+        ServicerCmd::Add { open_kfid, userids } => {
+            let userids: Vec<&str> = userids.iter().map(String::as_str).collect();
+            let req = AddServicer(ServicerReq::new(&open_kfid, &userids));
+            servicer_output(client.call(&req).await?)
+        }
+        ServicerCmd::Del { open_kfid, userids } => {
+            let userids: Vec<&str> = userids.iter().map(String::as_str).collect();
+            let req = DelServicer(ServicerReq::new(&open_kfid, &userids));
+            servicer_output(client.call(&req).await?)
+        }
+    }
+}
+
+async fn send(client: &Client, cmd: SendCmd) -> Result<Output, CliErr> {
+    let (target, message) = match cmd {
+        SendCmd::Text { target, content } => (target, OutgoingMessage::text(content)),
+        SendCmd::Image {
+            target,
+            media_id,
+            file,
+        } => {
+            let media_id = match (media_id, file) {
+                (Some(media_id), _) => media_id,
+                (None, Some(file)) => {
+                    let output = upload(client, &file, MediaType::Image).await?;
+                    output.json["media_id"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string()
+                }
+                (None, None) => {
+                    return Err(CliErr::Usage(
+                        "--media-id or --file is required".to_string(),
+                    ))
+                }
+            };
+            (target, OutgoingMessage::image(media_id))
+        }
+    };
+    let message = message
+        .to(target.to)
+        .from_kf(target.kf)
+        .build()
+        .map_err(|e| CliErr::Usage(e.to_string()))?;
+    let res = client.send(&message).await?;
+    check(res.errcode, &res.errmsg)?;
+    Ok(Output::new(json!({ "msgid": res.msgid }), &["msgid"]).row([res.msgid]))
+}
+
+/// 消息内容的摘要
+fn summary(item: &MsgItem) -> String {
+    match &item.message {
+        Message::Text { content, .. } => content.clone(),
+        Message::Image { media_id }
+        | Message::Voice { media_id }
+        | Message::Video { media_id }
+        | Message::File { media_id } => media_id.clone(),
+        Message::Event { event_type, .. } => format!("{event_type:?}"),
+        message => serde_json::to_value(message)
+            .ok()
+            .and_then(|value| value.get(&item.msgtype).map(Value::to_string))
+            .unwrap_or_default(),
+    }
+}
+
+const SYNC_HEADERS: &[&str] = &[
+    "msgid",
+    "send_time",
+    "origin",
+    "external_userid",
+    "msgtype",
+    "content",
+];
+
+fn sync_row(item: &MsgItem) -> Vec<String> {
+    vec![
+        item.msgid.clone(),
+        item.send_time.to_string(),
+        format!("{:?}", item.origin),
+        item.external_userid.clone().unwrap_or_default(),
+        item.msgtype.clone(),
+        summary(item),
+    ]
+}
+
+/// 拉取消息并逐页输出，表格格式下第一页前输出表头，JSON格式下每行一条消息
+async fn sync(session: &mut Session, args: SyncArgs, format: Format) -> Result<(), CliErr> {
+    let mut msg = SyncMsg {
+        cursor: args.cursor,
+        token: args.token,
+        limit: Some(args.limit),
+        voice_format: None,
+        open_kfid: Some(args.open_kfid),
+    };
+    let mut stdout = std::io::stdout();
+    if format == Format::Table {
+        writeln!(stdout, "{}", SYNC_HEADERS.join("  "))?;
+    }
+    loop {
+        let res = session.sync_msg(&msg).await?;
+        check(res.errcode, &res.errmsg)?;
+        for item in &res.msg_list {
+            let line = match format {
+                Format::Json => serde_json::to_string(item).unwrap_or_default(),
+                Format::Table => sync_row(item).join("  "),
+            };
+            writeln!(stdout, "{line}")?;
+        }
+        stdout.flush()?;
+        if !res.next_cursor.is_empty() {
+            msg.cursor = Some(res.next_cursor);
+        }
+        if res.has_more == MoreMsg::Yes {
+            continue;
+        }
+        if !args.follow {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+    }
+    if let Some(cursor) = msg.cursor {
+        eprintln!("next_cursor: {cursor}");
+    }
+    Ok(())
+}
+
+async fn media(client: &Client, cmd: MediaCmd) -> Result<Option<Output>, CliErr> {
+    match cmd {
+        MediaCmd::Upload { file, media_type } => upload(client, &file, media_type).await.map(Some),
+        MediaCmd::Get { media_id, output } => {
+            let res = client.call(&GetMedia::new(&media_id)).await?;
+            check(res.errcode, &res.errmsg)?;
+            match output {
+                Some(path) => std::fs::write(path, &res.data)?,
+                None => std::io::stdout().write_all(&res.data)?,
+            }
+            Ok(None)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliErr> {
+    let args = Config {
+        corpid: cli.corpid,
+        secret: cli.secret,
+        access_token: cli.access_token,
+        base_url: cli.base_url,
+    };
+    let config = Config::load(cli.config.as_deref())?.merge(args);
+    let output = match cli.command {
+        Command::Token => {
+            let client = with_base_url(Client::new(""), &config);
+            let (access_token, expires_in) = fetch_token(&client, &config).await?;
+            let access_token = access_token.expose().clone();
+            let json = json!({ "access_token": access_token, "expires_in": expires_in });
+            Some(
+                Output::new(json, &["access_token", "expires_in"])
+                    .row([access_token, expires_in.to_string()]),
+            )
+        }
+        Command::Account(cmd) => Some(account(&connect(&config).await?, cmd).await?),
+        Command::Servicer(cmd) => Some(servicer(&connect(&config).await?, cmd).await?),
+        Command::Send(cmd) => Some(send(&connect(&config).await?, cmd).await?),
+        Command::Sync(args) => {
+            sync(&mut Session::connect(config).await?, args, cli.format).await?;
+            None
+        }
+        Command::Media(cmd) => media(&connect(&config).await?, cmd).await?,
+    };
+    if let Some(output) = output {
+        println!("{}", output.render(cli.format));
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use kf_wx::transport::{BoxFuture, HttpRequest, HttpResponse, HttpTransport, TransportErr};
+    use std::sync::{Arc, Mutex};
+
+    /// 每次获取access_token返回新值，拉取消息时只接受最新的access_token
+    #[derive(Clone, Default)]
+    struct TokenStub {
+        urls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl HttpTransport for TokenStub {
+        fn send(&self, req: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportErr>> {
+            let mut urls = self.urls.lock().unwrap();
+            urls.push(req.url.clone());
+            let tokens = urls.iter().filter(|url| url.contains("/gettoken")).count();
+            let body = if req.url.contains("/gettoken") {
+                format!(
+                    r#"{{"errcode":0,"errmsg":"ok","access_token":"TOKEN{tokens}","expires_in":7200}}"#
+                )
+            } else if req.url.ends_with(&format!("access_token=TOKEN{tokens}")) {
+                r#"{"errcode":0,"errmsg":"ok","next_cursor":"","has_more":0,"msg_list":[]}"#
+                    .to_string()
+            } else {
+                r#"{"errcode":42001,"errmsg":"access_token expired"}"#.to_string()
+            };
+            Box::pin(async move { Ok(HttpResponse::ok(body)) })
+        }
+    }
+
+    impl TokenStub {
+        fn token_requests(&self) -> usize {
+            let urls = self.urls.lock().unwrap();
+            urls.iter().filter(|url| url.contains("/gettoken")).count()
+        }
+    }
+
+    async fn stub_session(stub: &TokenStub, config: Config) -> Session {
+        let transport = stub.clone();
+        let new_client: NewClient =
+            Box::new(move |token| Client::with_transport(token, transport.clone()));
+        Session::with_client(config, new_client).await.unwrap()
+    }
+
+    fn credentials() -> Config {
+        Config {
+            corpid: Some("CORPID".to_string()),
+            secret: Some(Secret::from("SECRET")),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_invalid_token() {
+        let stub = TokenStub::default();
+        let config = Config {
+            access_token: Some(Secret::from("STALE")),
+            ..credentials()
+        };
+        let mut session = stub_session(&stub, config).await;
+        let res = session.sync_msg(&SyncMsg::default()).await.unwrap();
+        assert_eq!(res.errcode, 0);
+        assert_eq!(stub.token_requests(), 1);
+
+        let stub = TokenStub::default();
+        let config = Config {
+            access_token: Some(Secret::from("STALE")),
+            ..Default::default()
+        };
+        let mut session = stub_session(&stub, config).await;
+        let res = session.sync_msg(&SyncMsg::default()).await.unwrap();
+        assert_eq!((res.errcode, stub.token_requests()), (42001, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_expiry() {
+        let stub = TokenStub::default();
+        let mut session = stub_session(&stub, credentials()).await;
+        session.sync_msg(&SyncMsg::default()).await.unwrap();
+        assert_eq!(stub.token_requests(), 1);
+
+        tokio::time::advance(Duration::from_secs(6800)).await;
+        session.sync_msg(&SyncMsg::default()).await.unwrap();
+        assert_eq!(stub.token_requests(), 1);
+
+        tokio::time::advance(Duration::from_secs(200)).await;
+        let res = session.sync_msg(&SyncMsg::default()).await.unwrap();
+        assert_eq!((res.errcode, stub.token_requests()), (0, 2));
+    }
+
+    #[test]
+    fn test_cli_err() {
+        assert_eq!(
+            CliErr::Api(40014, "invalid access_token".to_string()).to_string(),
+            "errcode 40014: invalid access_token"
+        );
+        let err = CliErr::Client(ClientErr::Api(45009, "api freq out of limit".to_string()));
+        assert_eq!(err.to_string(), "errcode 45009: api freq out of limit");
+        assert_eq!(
+            CliErr::Client(ClientErr::Status(502)).to_string(),
+            "unexpected HTTP status 502"
+        );
+    }
+
+    #[test]
+    fn test_cli_debug_redacted() {
+        let cli = Cli::try_parse_from([
+            "kf-wx",
+            "--corpid",
+            "CORPID",
+            "--secret",
+            "CORP_SECRET",
+            "--access-token",
+            "ACCESS_TOKEN",
+            "token",
+        ])
+        .unwrap();
+        assert_eq!(cli.secret.as_ref().unwrap().expose(), "CORP_SECRET");
+        let debug = format!("{cli:?}");
+        assert!(debug.contains("CORPID"));
+        assert!(!debug.contains("CORP_SECRET") && !debug.contains("ACCESS_TOKEN"));
+    }
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from([
+            "kf-wx",
+            "send",
+            "image",
+            "--to",
+            "USER",
+            "--kf",
+            "KF",
+            "--media-id",
+            "MEDIA",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Send(SendCmd::Image {
+                media_id: Some(_),
+                ..
+            })
+        ));
+        let result = Cli::try_parse_from(["kf-wx", "send", "image", "--to", "USER", "--kf", "KF"]);
+        assert!(result.is_err());
+
+        let cli =
+            Cli::try_parse_from(["kf-wx", "media", "upload", "a.amr", "--type", "voice"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Media(MediaCmd::Upload {
+                media_type: MediaType::Voice,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["kf-wx", "media", "upload", "a", "--type", "gif"]).is_err());
+
+        let cli =
+            Cli::try_parse_from(["kf-wx", "account", "update", "KF", "--name", "NAME"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Account(AccountCmd::Update {
+                name: Some(_),
+                media_id: None,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["kf-wx", "account", "update", "KF"]).is_err());
+    }
+
+    #[test]
+    fn test_sync_row() {
+        let res: MsgRes = serde_json::from_str(
+            r#"{
+                "errcode": 0,
+                "errmsg": "ok",
+                "next_cursor": "4gw7MepFLfgF2VC5npN",
+                "has_more": 0,
+                "msg_list": [{
+                    "msgid": "MSG_ID",
+                    "open_kfid": "OPEN_KFID",
+                    "external_userid": "EXTERNAL_USERID",
+                    "send_time": 1615478585,
+                    "origin": 3,
+                    "msgtype": "link",
+                    "link": { "title": "TITLE", "desc": "DESC", "url": "URL", "pic_url": "PIC_URL" }
+                }]
+            }"#,
+        )
+        .unwrap();
+        let row = sync_row(&res.msg_list[0]);
+        assert_eq!(
+            row[..5],
+            [
+                "MSG_ID",
+                "1615478585",
+                "WeiXinCustomer",
+                "EXTERNAL_USERID",
+                "link"
+            ]
+        );
+        assert!(row[5].contains("\"title\":\"TITLE\""));
+    }
+}
@@ -0,0 +1,99 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// 对齐的表格
+    Table,
+    /// 格式化的JSON
+    Json,
+}
+
+/// 命令的输出，同时保存JSON和表格两种形式
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub json: Value,
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Output {
+    pub fn new(json: Value, headers: &[&'static str]) -> Self {
+        Self {
+            json,
+            headers: headers.to_vec(),
+            rows: vec![],
+        }
+    }
+
+    pub fn row<I: IntoIterator<Item = S>, S: ToString>(mut self, row: I) -> Self {
+        self.rows
+            .push(row.into_iter().map(|cell| cell.to_string()).collect());
+        self
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Json => serde_json::to_string_pretty(&self.json).unwrap_or_default(),
+            Format::Table => table(&self.headers, &self.rows),
+        }
+    }
+}
+
+/// 字符在终端中的显示宽度，中日韩文字和全角符号占两列
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFF00..=0xFF60 => {
+            2
+        }
+        _ => 1,
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// 生成以两个空格分隔、按列对齐的表格
+pub fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| width(header)).collect();
+    for row in rows {
+        for (index, cell) in row.iter().enumerate() {
+            if let Some(width_) = widths.get_mut(index) {
+                *width_ = (*width_).max(width(cell));
+            }
+        }
+    }
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    std::iter::once(&headers)
+        .chain(rows)
+        .map(|row: &Vec<String>| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, column)| format!("{cell}{}", " ".repeat(column - width(cell))))
+                .collect();
+            line.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_table() {
+        let output = Output::new(json!([]), &["open_kfid", "name"])
+            .row(["wk1", "客服"])
+            .row(["wk_long", "a"]);
+        assert_eq!(
+            output.render(Format::Table),
+            "open_kfid  name\nwk1        客服\nwk_long    a"
+        );
+        assert_eq!(output.render(Format::Json), "[]");
+    }
+}
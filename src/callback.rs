@@ -0,0 +1,323 @@
+use std::sync::Arc;
+
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::decrypt::{decrypt_msg, Decrypt};
+use crate::encrypt::{encrypt_msg, random_bytes};
+use crate::metrics::MetricsSink;
+use crate::parse::{parse_callback_xml, WeiXinCallbackRes};
+use crate::secret::Secret;
+use crate::signature::{msg_signature, Signature};
+use crate::verify::{check_signature, VerifyErr, WeiXinCallbackParam};
+
+/// 接收回调后需要响应的内容，企业微信要求在5秒内响应
+pub const SUCCESS: &str = "success";
+
+/// 回调配置，对应管理后台设置的Token和EncodingAESKey，以及接收回调的企业ID
+#[derive(Clone)]
+pub struct CallbackConfig {
+    pub token: Secret<String>,
+    pub encoding_aes_key: Secret<String>,
+    /// 企业ID，解密得到的receiveid必须与之一致
+    pub corp_id: String,
+    metrics: Option<Arc<dyn MetricsSink>>,
+}
+
+impl std::fmt::Debug for CallbackConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackConfig")
+            .field("token", &self.token)
+            .field("encoding_aes_key", &self.encoding_aes_key)
+            .field("corp_id", &self.corp_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 加密的回调请求体
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Envelope {
+    encrypt: String,
+}
+
+impl CallbackConfig {
+    pub fn new(token: &str, encoding_aes_key: &str, corp_id: &str) -> Self {
+        Self {
+            token: Secret::from(token),
+            encoding_aes_key: Secret::from(encoding_aes_key),
+            corp_id: corp_id.to_string(),
+            metrics: None,
+        }
+    }
+
+    /// 设置指标收集器，记录校验失败的回调请求
+    pub fn metrics<M: MetricsSink>(mut self, sink: M) -> Self {
+        self.metrics = Some(Arc::new(sink));
+        self
+    }
+
+    /// 记录验证和解密的结果
+    fn record<T>(&self, action: &str, result: &Result<T, VerifyErr>) {
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(_) => tracing::debug!(action, "callback accepted"),
+            Err(err) => tracing::warn!(action, error = ?err, "callback rejected"),
+        }
+        if let (Err(_), Some(metrics)) = (result, &self.metrics) {
+            metrics.callback_rejected(action);
+        }
+    }
+
+    /// 验证URL有效性，返回需要原样响应的echostr明文
+    pub fn verify(&self, params: &WeiXinCallbackParam) -> Result<String, VerifyErr> {
+        let result = self
+            .open(params, &params.echostr)
+            .map(|decrypt| decrypt.msg);
+        self.record("verify", &result);
+        result
+    }
+
+    /// 校验签名，解密后校验receiveid
+    fn open(&self, params: &WeiXinCallbackParam, encrypt: &str) -> Result<Decrypt, VerifyErr> {
+        check_signature(params, self.token.expose(), encrypt)?;
+        let decrypt = decrypt_msg(encrypt, self.encoding_aes_key.expose())?;
+        if decrypt.receiveid != self.corp_id {
+            return Err(VerifyErr::CorpId(decrypt.receiveid));
+        }
+        Ok(decrypt)
+    }
+
+    /// 校验签名并解密回调请求体
+    pub fn decrypt(
+        &self,
+        params: &WeiXinCallbackParam,
+        body: &str,
+    ) -> Result<WeiXinCallbackRes, VerifyErr> {
+        let result = self.decrypt_body(params, body);
+        self.record("decrypt", &result);
+        result
+    }
+
+    fn decrypt_body(
+        &self,
+        params: &WeiXinCallbackParam,
+        body: &str,
+    ) -> Result<WeiXinCallbackRes, VerifyErr> {
+        let envelope: Envelope = from_str(body).map_err(|e| VerifyErr::Xml(e.to_string()))?;
+        let msg = self.open(params, &envelope.encrypt)?.msg;
+        parse_callback_xml(&msg).map_err(|e| VerifyErr::Xml(e.to_string()))
+    }
+
+    /// 加密回调内容，返回请求体和对应的URL参数
+    pub fn encrypt(
+        &self,
+        receiveid: &str,
+        xml: &str,
+        timestamp: &str,
+        nonce: &str,
+    ) -> Result<(String, WeiXinCallbackParam), VerifyErr> {
+        let encrypt = encrypt_msg(
+            xml,
+            receiveid,
+            self.encoding_aes_key.expose(),
+            &random_bytes(),
+        )?;
+        let signature = Signature::new(self.token.expose(), timestamp, nonce, &encrypt);
+        let params = WeiXinCallbackParam {
+            timestamp: timestamp.to_string(),
+            nonce: nonce.to_string(),
+            echostr: String::new(),
+            msg_signature: msg_signature(&signature),
+        };
+        let body = format!(
+            "<xml><ToUserName><![CDATA[{receiveid}]]></ToUserName><Encrypt><![CDATA[{encrypt}]]></Encrypt><AgentID><![CDATA[]]></AgentID></xml>"
+        );
+        Ok((body, params))
+    }
+}
+
+/// 回调事件的接收者，应尽快返回，耗时的处理（如拉取消息）应异步进行
+pub trait EventSink: Send + Sync + 'static {
+    /// 接收回调事件，返回`false`表示暂时无法接收，企业微信会稍后重试
+    fn push(&self, event: WeiXinCallbackRes) -> bool;
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(WeiXinCallbackRes) + Send + Sync + 'static,
+{
+    fn push(&self, event: WeiXinCallbackRes) -> bool {
+        self(event);
+        true
+    }
+}
+
+impl EventSink for mpsc::Sender<WeiXinCallbackRes> {
+    fn push(&self, event: WeiXinCallbackRes) -> bool {
+        self.try_send(event).is_ok()
+    }
+}
+
+impl EventSink for mpsc::UnboundedSender<WeiXinCallbackRes> {
+    fn push(&self, event: WeiXinCallbackRes) -> bool {
+        self.send(event).is_ok()
+    }
+}
+
+/// 基于`http`类型的回调处理器，可用于hyper、warp、lambda等任意框架
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct CallbackHandler<S> {
+    config: CallbackConfig,
+    sink: S,
+}
+
+#[cfg(feature = "http")]
+impl<S: EventSink> CallbackHandler<S> {
+    /// 解析后的回调事件交给`sink`处理
+    pub fn new(config: CallbackConfig, sink: S) -> Self {
+        Self { config, sink }
+    }
+
+    /// 处理回调请求，GET请求用于验证URL，POST请求用于接收回调
+    pub fn handle(&self, req: http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes> {
+        use http::{Method, StatusCode};
+
+        if !matches!(*req.method(), Method::GET | Method::POST) {
+            return response(StatusCode::METHOD_NOT_ALLOWED, "");
+        }
+        let query = req.uri().query().unwrap_or_default();
+        let params = match serde_urlencoded::from_str::<WeiXinCallbackParam>(query) {
+            Ok(params) => params,
+            Err(_) => return response(StatusCode::BAD_REQUEST, ""),
+        };
+        match *req.method() {
+            Method::GET => match self.config.verify(&params) {
+                Ok(msg) => response(StatusCode::OK, &msg),
+                Err(err) => error_response(&err),
+            },
+            Method::POST => {
+                let Ok(body) = std::str::from_utf8(req.body()) else {
+                    return response(StatusCode::BAD_REQUEST, "");
+                };
+                match self
+                    .config
+                    .decrypt(&params, body)
+                    .map(|e| self.sink.push(e))
+                {
+                    Ok(true) => response(StatusCode::OK, SUCCESS),
+                    Ok(false) => response(StatusCode::SERVICE_UNAVAILABLE, ""),
+                    Err(err) => error_response(&err),
+                }
+            }
+            _ => response(StatusCode::METHOD_NOT_ALLOWED, ""),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn error_response(err: &VerifyErr) -> http::Response<bytes::Bytes> {
+    let status = http::StatusCode::from_u16(err.status()).unwrap_or(http::StatusCode::BAD_REQUEST);
+    response(status, "")
+}
+
+#[cfg(feature = "http")]
+fn response(status: http::StatusCode, body: &str) -> http::Response<bytes::Bytes> {
+    let mut res = http::Response::new(bytes::Bytes::copy_from_slice(body.as_bytes()));
+    *res.status_mut() = status;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let config = CallbackConfig::new(
+            "TOKEN",
+            "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C",
+            "CORP_ID",
+        );
+        let event = WeiXinCallbackRes::new("CORP_ID", "TOKEN_VALUE", "OPEN_KFID");
+        let (body, params) = config
+            .encrypt("CORP_ID", &event.to_xml(), "1409659813", "1372623149")
+            .unwrap();
+        let result = config.decrypt(&params, &body).unwrap();
+        assert_eq!(result.token, "TOKEN_VALUE");
+        assert_eq!(result.open_kf_id, "OPEN_KFID");
+
+        let other = CallbackConfig::new("OTHER", config.encoding_aes_key.expose(), "CORP_ID");
+        assert_eq!(other.decrypt(&params, &body), Err(VerifyErr::Signature));
+    }
+
+    #[test]
+    fn test_corp_id_mismatch() {
+        let config = CallbackConfig::new(
+            "TOKEN",
+            "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C",
+            "CORP_ID",
+        );
+        let event = WeiXinCallbackRes::new("OTHER_CORP_ID", "TOKEN_VALUE", "OPEN_KFID");
+        let (body, params) = config
+            .encrypt("OTHER_CORP_ID", &event.to_xml(), "1409659813", "1372623149")
+            .unwrap();
+        let err = config.decrypt(&params, &body).unwrap_err();
+        assert_eq!(err, VerifyErr::CorpId("OTHER_CORP_ID".to_string()));
+        assert_eq!(err.status(), 403);
+    }
+
+    #[test]
+    fn test_debug_redacted() {
+        let config = CallbackConfig::new(
+            "TOKEN",
+            "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C",
+            "CORP_ID",
+        );
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("TOKEN"));
+        assert!(!debug.contains("jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_callback_handler() {
+        use http::{Request, StatusCode};
+
+        let config = CallbackConfig::new(
+            "TOKEN",
+            "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C",
+            "CORP_ID",
+        );
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let handler = CallbackHandler::new(config.clone(), sender);
+        let event = WeiXinCallbackRes::new("CORP_ID", "TOKEN_VALUE", "OPEN_KFID");
+        let (body, params) = config
+            .encrypt("CORP_ID", &event.to_xml(), "1409659813", "1372623149")
+            .unwrap();
+        let uri = format!(
+            "/callback?msg_signature={}&timestamp={}&nonce={}",
+            params.msg_signature, params.timestamp, params.nonce
+        );
+
+        let req = Request::post(&uri).body(body.clone().into()).unwrap();
+        let res = handler.handle(req);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.body(), SUCCESS);
+        assert_eq!(receiver.try_recv().unwrap(), event);
+
+        let req = Request::get(&uri).body(body.into()).unwrap();
+        let res = handler.handle(req);
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let req = Request::post("/callback").body("".into()).unwrap();
+        let res = handler.handle(req);
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = Request::put("/callback").body("".into()).unwrap();
+        let res = handler.handle(req);
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
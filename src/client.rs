@@ -0,0 +1,585 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::account::SimpleRes;
+use crate::constant::API_URL;
+use crate::endpoint::Endpoint;
+use crate::metrics::MetricsSink;
+use crate::msg_res::{MsgItem, ReplyErr};
+use crate::msgid::MsgidGenerator;
+use crate::rate_limit::RateLimiter;
+use crate::recall::{RecallErr, RecallRequest};
+use crate::receive::{MsgRes, SyncMsg};
+use crate::retry::{errcode, RetryPolicy};
+use crate::secret::Secret;
+use crate::send::{BuildErr, Message, MessageRes, MsgType};
+use crate::token::GET_TOKEN_PATH;
+use crate::transport::{HttpResponse, HttpTransport, TransportErr};
+use crate::welcome::{Welcome, WelcomeRes};
+use crate::window::{WindowErr, WindowTracker};
+
+/// 客户端错误类型
+#[derive(Debug)]
+pub enum ClientErr {
+    Transport(TransportErr),
+    /// 响应的HTTP状态码不是2xx
+    Status(u16),
+    /// 响应内容无法解析
+    Decode(String),
+    Reply(ReplyErr),
+    Recall(RecallErr),
+    /// 接口返回的错误：(errcode, errmsg)
+    Api(i32, String),
+    /// 超出客户端限流额度，需要等待的时间，并发请求数已满时为0
+    RateLimited(Duration),
+    /// 超出客户的发送窗口
+    Window(WindowErr),
+}
+
+impl std::fmt::Display for ClientErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientErr::Transport(err) => err.fmt(f),
+            ClientErr::Status(status) => write!(f, "unexpected HTTP status {status}"),
+            ClientErr::Decode(message) => write!(f, "failed to decode response: {message}"),
+            ClientErr::Reply(err) => err.fmt(f),
+            ClientErr::Recall(err) => err.fmt(f),
+            ClientErr::Api(errcode, errmsg) => write!(f, "errcode {errcode}: {errmsg}"),
+            ClientErr::RateLimited(wait) => {
+                write!(f, "rate limited, retry in {}ms", wait.as_millis())
+            }
+            ClientErr::Window(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ClientErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientErr::Transport(err) => Some(err),
+            ClientErr::Reply(err) => Some(err),
+            ClientErr::Recall(err) => Some(err),
+            ClientErr::Window(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<TransportErr> for ClientErr {
+    fn from(value: TransportErr) -> Self {
+        ClientErr::Transport(value.redacted())
+    }
+}
+
+impl From<ReplyErr> for ClientErr {
+    fn from(value: ReplyErr) -> Self {
+        ClientErr::Reply(value)
+    }
+}
+
+impl From<WindowErr> for ClientErr {
+    fn from(value: WindowErr) -> Self {
+        ClientErr::Window(value)
+    }
+}
+
+impl From<RecallErr> for ClientErr {
+    fn from(value: RecallErr) -> Self {
+        ClientErr::Recall(value)
+    }
+}
+
+/// 持有access_token的客服接口客户端
+#[derive(Clone)]
+pub struct Client {
+    token: Secret<String>,
+    base_url: String,
+    transport: Arc<dyn HttpTransport>,
+    msgid: Arc<MsgidGenerator>,
+    duplicate_msgid_errcode: Option<i32>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    window: Option<WindowTracker>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+}
+
+impl Client {
+    /// 使用默认的reqwest传输层
+    #[cfg(feature = "reqwest")]
+    pub fn new(token: &str) -> Self {
+        Self::with_transport(token, crate::transport::ReqwestTransport::default())
+    }
+
+    /// 使用自定义的传输层
+    pub fn with_transport<T: HttpTransport>(token: &str, transport: T) -> Self {
+        Self {
+            token: Secret::from(token),
+            base_url: API_URL.to_string(),
+            transport: Arc::new(transport),
+            msgid: Arc::new(MsgidGenerator::default()),
+            duplicate_msgid_errcode: None,
+            retry: RetryPolicy::default(),
+            limiter: None,
+            window: None,
+            metrics: None,
+        }
+    }
+
+    /// 设置自动生成的msgid的前缀
+    pub fn msgid_prefix(mut self, prefix: &str) -> Result<Self, BuildErr> {
+        self.msgid = Arc::new(MsgidGenerator::new(prefix)?);
+        Ok(self)
+    }
+
+    /// 设置接口地址，默认为`https://qyapi.weixin.qq.com/cgi-bin`
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// 设置msgid重复时接口返回的错误码，设置后[`Client::send`]把该错误视为已发送成功
+    ///
+    /// 接口文档没有列出msgid重复对应的错误码，默认不做特殊处理，需按实际返回的错误码设置。
+    /// 测试中使用的`fixtures::DUPLICATE_MSGID`是模拟服务的占位值，
+    /// 不是真实接口的错误码。
+    pub fn duplicate_msgid_errcode(mut self, errcode: i32) -> Self {
+        self.duplicate_msgid_errcode = Some(errcode);
+        self
+    }
+
+    /// 设置请求失败时的重试策略
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// 设置限流器，克隆出的客户端共享同一个限流器
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    /// 设置发送窗口跟踪器，发送消息前在本地校验客户的发送额度
+    pub fn send_window(mut self, tracker: WindowTracker) -> Self {
+        self.window = Some(tracker);
+        self
+    }
+
+    /// 设置指标收集器
+    pub fn metrics<M: MetricsSink>(mut self, sink: M) -> Self {
+        self.metrics = Some(Arc::new(sink));
+        self
+    }
+
+    pub(crate) fn metrics_sink(&self) -> Option<&dyn MetricsSink> {
+        self.metrics.as_deref()
+    }
+
+    /// 通过传输层请求任意接口并解析响应，失败时按重试策略重试
+    pub async fn call<E: Endpoint>(&self, endpoint: &E) -> Result<E::Response, ClientErr> {
+        let path = endpoint.path();
+        let name = path.split('?').next().unwrap_or_default();
+        let start = Instant::now();
+        let future = self.execute(endpoint);
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "kf_wx.call",
+            endpoint = name,
+            open_kfid = endpoint.open_kfid(),
+            errcode = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::instrument(future, span.clone());
+        let result = future.await.and_then(|res| {
+            let errcode = errcode(&res.body);
+            E::parse_response(&res.body)
+                .map(|res| (res, errcode))
+                .map_err(|e| ClientErr::Decode(e.to_string()))
+        });
+        let latency = start.elapsed();
+        let errcode = result.as_ref().ok().and_then(|(_, errcode)| *errcode);
+        #[cfg(feature = "tracing")]
+        {
+            span.record("latency_ms", latency.as_millis() as u64);
+            if let Some(errcode) = errcode {
+                span.record("errcode", errcode);
+            }
+            if let Err(err) = &result {
+                tracing::warn!(parent: &span, error = ?err, "api call failed");
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.api_call(name, latency, errcode);
+            if name == GET_TOKEN_PATH && errcode == Some(0) {
+                metrics.token_refresh();
+            }
+        }
+        result.map(|(res, _)| res)
+    }
+
+    /// 发送请求，按重试策略重试，返回状态码为2xx的响应
+    async fn execute<E: Endpoint>(&self, endpoint: &E) -> Result<HttpResponse, ClientErr> {
+        let idempotent = endpoint.idempotent();
+        let mut attempt = 1;
+        let res = loop {
+            let mut permit = None;
+            if let Some(limiter) = &self.limiter {
+                permit = limiter
+                    .enter(endpoint)
+                    .await
+                    .map_err(ClientErr::RateLimited)?;
+                limiter
+                    .acquire(endpoint)
+                    .await
+                    .map_err(ClientErr::RateLimited)?;
+            }
+            let req = endpoint.to_request(&self.base_url, self.token.expose());
+            let result = self.transport.send(req).await;
+            drop(permit);
+            if let (Some(limiter), Ok(res)) = (&self.limiter, &result) {
+                limiter.record(endpoint, errcode(&res.body));
+            }
+            if !self.retry.should_retry(attempt, &result, idempotent) {
+                break result?;
+            }
+            let delay = self.retry.delay(attempt);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "retrying api call"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+        if !(200..300).contains(&res.status) {
+            return Err(ClientErr::Status(res.status));
+        }
+        Ok(res)
+    }
+
+    /// 为未指定msgid的消息生成msgid，返回消息最终使用的msgid
+    ///
+    /// 调用方需要自行重试发送时，应在第一次发送前调用，之后每次重试都使用同一个消息。
+    pub fn assign_msgid(&self, message: &mut Message) -> String {
+        message
+            .msgid
+            .get_or_insert_with(|| self.msgid.generate())
+            .clone()
+    }
+
+    /// 发送消息
+    ///
+    /// 未指定msgid时自动生成，按[`RetryPolicy`]进行的重试都使用该msgid，
+    /// 接口返回错误码时响应中的`msgid`也是本次使用的msgid；
+    /// 生成的msgid不会写回`message`，调用方自行重试前需先调用[`Client::assign_msgid`]。
+    /// 设置了[`Client::duplicate_msgid_errcode`]时，msgid重复视为已发送成功，返回原msgid。
+    /// 设置了发送窗口时，超出额度的消息直接返回错误而不请求接口。
+    pub async fn send(&self, message: &Message) -> Result<MessageRes, ClientErr> {
+        let mut message = message.clone();
+        let msgid = self.assign_msgid(&mut message);
+        let reserved = match &self.window {
+            Some(window) => Some(window.reserve(&message.open_kfid, &message.touser)?),
+            None => None,
+        };
+        let future = self.call(&message);
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::instrument(
+            future,
+            tracing::info_span!("kf_wx.send", msgid = %msgid),
+        );
+        let result = future.await.map(|res| {
+            if Some(res.errcode) == self.duplicate_msgid_errcode {
+                MessageRes {
+                    errcode: 0,
+                    errmsg: "ok".to_string(),
+                    msgid,
+                }
+            } else if res.msgid.is_empty() {
+                MessageRes { msgid, ..res }
+            } else {
+                res
+            }
+        });
+        if let (Some(window), Some(quota)) = (&self.window, &reserved) {
+            if !matches!(&result, Ok(res) if res.errcode == 0) {
+                window.release(&message.open_kfid, &message.touser, quota);
+            }
+        }
+        result
+    }
+
+    /// 回复客户发送的消息
+    pub async fn reply(&self, item: &MsgItem, msgtype: MsgType) -> Result<MessageRes, ClientErr> {
+        let message = item.reply(msgtype)?;
+        self.send(&message).await
+    }
+
+    /// 发送欢迎语等事件响应消息
+    pub async fn send_welcome(&self, welcome: &Welcome) -> Result<WelcomeRes, ClientErr> {
+        self.call(welcome).await
+    }
+
+    /// 撤回消息，超过可撤回时间窗口时直接返回错误而不请求接口
+    pub async fn recall(&self, req: &RecallRequest) -> Result<SimpleRes, ClientErr> {
+        req.check_window()?;
+        self.call(req).await
+    }
+
+    /// 拉取消息
+    pub async fn sync_msg(&self, msg: &SyncMsg) -> Result<MsgRes, ClientErr> {
+        self.call(msg).await
+    }
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("token", &self.token)
+            .field("base_url", &self.base_url)
+            .field("msgid", &self.msgid)
+            .field("duplicate_msgid_errcode", &self.duplicate_msgid_errcode)
+            .field("retry", &self.retry)
+            .field("limiter", &self.limiter)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::send;
+    use crate::transport::mock::{mock, mock_seq};
+    use crate::transport::Method;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    fn no_delay() -> RetryPolicy {
+        RetryPolicy::default().backoff(Duration::ZERO, Duration::ZERO)
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let transport = mock(r#"{"errcode":0,"errmsg":"ok","msgid":"MSG_ID"}"#);
+        let client = Client::with_transport("TOKEN", transport.clone())
+            .msgid_prefix("kf")
+            .unwrap();
+        let message = send::OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let res = client.send(&message).await.unwrap();
+        assert_eq!(res.msgid, "MSG_ID");
+
+        let requests = transport.requests.lock().unwrap();
+        let req = &requests[0];
+        assert_eq!(req.method, Method::Post);
+        assert_eq!(
+            req.url,
+            "https://qyapi.weixin.qq.com/cgi-bin/kf/send_msg?access_token=TOKEN"
+        );
+        let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+        assert!(body["msgid"].as_str().unwrap().starts_with("kf"));
+    }
+
+    #[test]
+    fn test_client_err_display() {
+        fn boxed(err: ClientErr) -> Result<(), Box<dyn std::error::Error>> {
+            Err(err)?
+        }
+        let err = boxed(ClientErr::Api(40014, "invalid access_token".to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "errcode 40014: invalid access_token");
+
+        let err = ClientErr::from(WindowErr::Expired(1700000000));
+        assert_eq!(err.to_string(), "the send window ended at 1700000000");
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), err.to_string());
+        let err = ClientErr::from(RecallErr::Expired(130));
+        assert_eq!(
+            err.to_string(),
+            "message was sent 130s ago, recall is allowed within 120s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_msgid() {
+        // 占位错误码，接口文档没有列出msgid重复对应的错误码
+        const DUPLICATE: i32 = 99999;
+        let transport = mock(r#"{"errcode":99999,"errmsg":"duplicate msgid"}"#);
+        let message = send::OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .msgid("MSG_ID")
+            .build()
+            .unwrap();
+        let client = Client::with_transport("TOKEN", transport.clone());
+        let res = client.send(&message).await.unwrap();
+        assert_eq!(res.errcode, DUPLICATE);
+
+        let client = client.duplicate_msgid_errcode(DUPLICATE);
+        let res = client.send(&message).await.unwrap();
+        assert_eq!(res.errcode, 0);
+        assert_eq!(res.msgid, "MSG_ID");
+    }
+
+    #[tokio::test]
+    async fn test_assign_msgid() {
+        let transport = mock(r#"{"errcode":-1,"errmsg":"system busy"}"#);
+        let client = Client::with_transport("TOKEN", transport.clone()).retry(RetryPolicy::none());
+        let mut message = send::OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let msgid = client.assign_msgid(&mut message);
+        assert_eq!(message.msgid.as_deref(), Some(msgid.as_str()));
+        assert_eq!(client.assign_msgid(&mut message), msgid);
+
+        let res = client.send(&message).await.unwrap();
+        assert_eq!((res.errcode, res.msgid.as_str()), (-1, msgid.as_str()));
+        client.send(&message).await.unwrap();
+
+        message.msgid = None;
+        let res = client.send(&message).await.unwrap();
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].body, requests[1].body);
+        let body: serde_json::Value = serde_json::from_slice(&requests[2].body).unwrap();
+        assert_eq!(body["msgid"], res.msgid.as_str());
+        assert_ne!(res.msgid, msgid);
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let transport =
+            mock(r#"{"errcode":0,"errmsg":"ok","access_token":"ACCESS_TOKEN","expires_in":7200}"#);
+        let client =
+            Client::with_transport("TOKEN", transport.clone()).base_url("http://localhost/");
+        let res = client
+            .call(&crate::GetToken::new("ID", "SECRET"))
+            .await
+            .unwrap();
+        assert_eq!(res.access_token.expose(), "ACCESS_TOKEN");
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].method, Method::Get);
+        assert_eq!(
+            requests[0].url,
+            "http://localhost/gettoken?corpid=ID&corpsecret=SECRET"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_send() {
+        let busy = r#"{"errcode":-1,"errmsg":"system busy"}"#;
+        let transport = mock_seq(&[
+            busy,
+            busy,
+            r#"{"errcode":0,"errmsg":"ok","msgid":"MSG_ID"}"#,
+        ]);
+        let client = Client::with_transport("TOKEN", transport.clone()).retry(no_delay());
+        let message = send::OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let res = client.send(&message).await.unwrap();
+        assert_eq!(res.errcode, 0);
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|req| req.body == requests[0].body));
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_without_msgid() {
+        let transport = mock(r#"{"errcode":-1,"errmsg":"system busy"}"#);
+        let client = Client::with_transport("TOKEN", transport.clone()).retry(no_delay());
+        let message = send::OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let res = client.call(&message).await.unwrap();
+        assert_eq!(res.errcode, -1);
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited() {
+        use crate::rate_limit::{Limit, Mode};
+
+        let transport = mock(r#"{"errcode":0,"errmsg":"ok","msg_list":[]}"#);
+        let limiter = RateLimiter::new(Mode::FailFast).limit("/kf/sync_msg", Limit::per_minute(1));
+        let client = Client::with_transport("TOKEN", transport.clone()).rate_limit(limiter);
+        client.sync_msg(&SyncMsg::default()).await.unwrap();
+        let err = client
+            .clone()
+            .sync_msg(&SyncMsg::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientErr::RateLimited(_)));
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_throttled() {
+        use crate::rate_limit::Mode;
+
+        let transport = mock(r#"{"errcode":45009,"errmsg":"api freq out of limit"}"#);
+        let limiter = RateLimiter::wecom(Mode::FailFast);
+        let client = Client::with_transport("TOKEN", transport.clone()).rate_limit(limiter);
+        let res = client.sync_msg(&SyncMsg::default()).await.unwrap();
+        assert_eq!(res.errcode, 45009);
+        let err = client.sync_msg(&SyncMsg::default()).await.unwrap_err();
+        assert!(matches!(err, ClientErr::RateLimited(wait) if wait <= Duration::from_secs(1)));
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_window() {
+        use crate::window::MemoryWindowStore;
+
+        let transport = mock(r#"{"errcode":0,"errmsg":"ok","msgid":"MSG_ID"}"#);
+        let tracker = WindowTracker::new(Arc::new(MemoryWindowStore::default()));
+        let client = Client::with_transport("TOKEN", transport.clone()).send_window(tracker);
+        let message = send::OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let err = client.send(&message).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ClientErr::Window(WindowErr::NoCustomerMessage)
+        ));
+        assert!(transport.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<(String, Option<i32>)>>);
+
+        impl MetricsSink for Recorder {
+            fn api_call(&self, endpoint: &str, _latency: Duration, errcode: Option<i32>) {
+                self.0.lock().unwrap().push((endpoint.to_string(), errcode));
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let transport = mock(r#"{"errcode":40014,"errmsg":"invalid access_token"}"#);
+        let client = Client::with_transport("TOKEN", transport).metrics(recorder.clone());
+        let res = client
+            .call(&crate::GetToken::new("ID", "SECRET"))
+            .await
+            .unwrap();
+        assert_eq!(res.errcode, 40014);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [("/gettoken".to_string(), Some(40014))]
+        );
+    }
+}
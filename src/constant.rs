@@ -1 +1,2 @@
-pub const BASE_URL: &str = "https://qyapi.weixin.qq.com/cgi-bin/kf";
+/// 企业微信接口地址
+pub const API_URL: &str = "https://qyapi.weixin.qq.com/cgi-bin";
@@ -101,7 +101,7 @@ fn decrypt_orig_msg(msg: &str, ase_key: &[u8]) -> Result<Vec<u8>, DecryptErr> {
     let iv = &ase_key[..16];
     let key = &ase_key[..32];
     let cipher = Aes256CbcDec::new_from_slices(key, iv)?;
-    let mut buffer = vec![0u8; 1024];
+    let mut buffer = vec![0u8; result.len()];
     let result = cipher
         .decrypt_padded_b2b_mut::<NoPadding>(&result, &mut buffer)
         .map(|v| v.to_vec())?;
@@ -109,7 +109,7 @@ fn decrypt_orig_msg(msg: &str, ase_key: &[u8]) -> Result<Vec<u8>, DecryptErr> {
 }
 
 /// 解密ase_key
-fn decrypt_ase_key(encode_ase_key: &str) -> Result<Vec<u8>, DecodeError> {
+pub(crate) fn decrypt_ase_key(encode_ase_key: &str) -> Result<Vec<u8>, DecodeError> {
     let encode_ase_key = format!("{}=", encode_ase_key);
     G.decode(encode_ase_key)
 }
@@ -131,4 +131,20 @@ mod test {
         };
         assert_eq!(result, Ok(expected));
     }
+
+    /// 超过1024字节的消息曾因解密缓冲区大小固定而解密失败
+    #[test]
+    fn test_decode_long_msg() {
+        let encode_ase_key = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+        let msg = format!(
+            "<xml><Content><![CDATA[{}]]></Content></xml>",
+            "a".repeat(4096)
+        );
+        let encrypted =
+            crate::encrypt::encrypt_msg(&msg, "wx5823bf96d3bd56c7", encode_ase_key, &[0u8; 16])
+                .unwrap();
+        let result = decrypt_msg(&encrypted, encode_ase_key).unwrap();
+        assert_eq!(result.msg, msg);
+        assert_eq!(result.receiveid, "wx5823bf96d3bd56c7");
+    }
 }
@@ -0,0 +1,349 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::account::SimpleRes;
+use crate::client::{Client, ClientErr};
+use crate::middleware::{Middleware, Next};
+use crate::msg_res::{KfEvent, MsgItem};
+use crate::recall::RecallRequest;
+use crate::send::{MessageRes, MsgType};
+
+pub use crate::transport::BoxFuture;
+
+/// 处理器的执行结果
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Flow {
+    /// 消息已处理，不再交给后续处理器
+    Stop,
+    /// 继续交给下一个匹配的处理器
+    Next,
+}
+
+/// 处理器的上下文，携带消息和客户端
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub item: Arc<MsgItem>,
+    pub client: Client,
+}
+
+impl Context {
+    pub fn new(client: Client, item: MsgItem) -> Self {
+        Self {
+            item: Arc::new(item),
+            client,
+        }
+    }
+
+    /// 回复当前消息
+    pub async fn reply(&self, msgtype: MsgType) -> Result<MessageRes, ClientErr> {
+        self.client.reply(&self.item, msgtype).await
+    }
+
+    /// 回复文本消息
+    pub async fn reply_text(&self, content: &str) -> Result<MessageRes, ClientErr> {
+        self.reply(MsgType::Text(content.to_string())).await
+    }
+
+    /// 撤回当前消息
+    pub async fn recall(&self) -> Result<SimpleRes, ClientErr> {
+        let req = RecallRequest::from_item(&self.item)?;
+        self.client.recall(&req).await
+    }
+}
+
+type Predicate = Box<dyn Fn(&MsgItem) -> bool + Send + Sync>;
+type Handler = Box<dyn Fn(Context) -> BoxFuture<'static, Flow> + Send + Sync>;
+
+struct Route {
+    predicate: Predicate,
+    handler: Handler,
+}
+
+/// 消息分发器，按注册顺序将消息交给匹配的处理器
+///
+/// 处理器返回[`Flow::Next`]时消息继续交给下一个匹配的处理器，
+/// 没有处理器匹配或所有处理器都返回[`Flow::Next`]时交给兜底处理器。
+/// 消息在交给处理器之前会依次经过通过[`Dispatcher::layer`]添加的中间件。
+#[derive(Default)]
+pub struct Dispatcher {
+    middlewares: Vec<Arc<dyn Middleware>>,
+    routes: Vec<Route>,
+    fallback: Option<Handler>,
+}
+
+fn boxed<F, Fut>(handler: F) -> Handler
+where
+    F: Fn(Context) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Flow> + Send + 'static,
+{
+    Box::new(move |ctx| Box::pin(handler(ctx)))
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加中间件，先添加的中间件在外层
+    pub fn layer<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// 注册处理器，`predicate`返回`true`的消息交给该处理器
+    pub fn on<P, F, Fut>(mut self, predicate: P, handler: F) -> Self
+    where
+        P: Fn(&MsgItem) -> bool + Send + Sync + 'static,
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.routes.push(Route {
+            predicate: Box::new(predicate),
+            handler: boxed(handler),
+        });
+        self
+    }
+
+    /// 处理文本消息
+    pub fn on_text<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_text(), handler)
+    }
+
+    /// 处理内容匹配正则表达式的文本消息
+    pub fn on_text_matching<F, Fut>(self, regex: Regex, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        let predicate = move |item: &MsgItem| {
+            item.message
+                .text_content()
+                .is_some_and(|content| regex.is_match(content))
+        };
+        self.on(predicate, handler)
+    }
+
+    /// 处理图片消息
+    pub fn on_image<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_image(), handler)
+    }
+
+    /// 处理语音消息
+    pub fn on_voice<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_voice(), handler)
+    }
+
+    /// 处理视频消息
+    pub fn on_video<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_video(), handler)
+    }
+
+    /// 处理文件消息
+    pub fn on_file<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_file(), handler)
+    }
+
+    /// 处理位置消息
+    pub fn on_location<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_location(), handler)
+    }
+
+    /// 处理链接消息
+    pub fn on_link<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_link(), handler)
+    }
+
+    /// 处理指定类型的事件
+    pub fn on_event<F, Fut>(self, event: KfEvent, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(
+            move |item| item.message.event_type() == Some(event),
+            handler,
+        )
+    }
+
+    /// 处理所有事件
+    pub fn on_any_event<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.on(|item| item.message.is_event(), handler)
+    }
+
+    /// 兜底处理器，处理没有被其它处理器处理的消息
+    pub fn fallback<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Flow> + Send + 'static,
+    {
+        self.fallback = Some(boxed(handler));
+        self
+    }
+
+    /// 分发一条消息，返回[`Flow::Stop`]表示消息已被处理或被中间件丢弃，[`Flow::Next`]表示没有处理器处理
+    pub async fn dispatch(&self, client: &Client, item: MsgItem) -> Flow {
+        let ctx = Context::new(client.clone(), item);
+        self.dispatch_ctx(ctx).await
+    }
+
+    /// 按顺序分发多条消息
+    pub async fn dispatch_all(&self, client: &Client, items: Vec<MsgItem>) {
+        for item in items {
+            self.dispatch(client, item).await;
+        }
+    }
+
+    /// 使用已有的上下文分发消息
+    pub async fn dispatch_ctx(&self, ctx: Context) -> Flow {
+        Next::new(&self.middlewares, self).run(ctx).await
+    }
+
+    /// 不经过中间件，直接将消息交给处理器
+    pub(crate) async fn route(&self, ctx: Context) -> Flow {
+        for route in &self.routes {
+            if (route.predicate)(&ctx.item) && (route.handler)(ctx.clone()).await == Flow::Stop {
+                return Flow::Stop;
+            }
+        }
+        match &self.fallback {
+            Some(fallback) => fallback(ctx).await,
+            None => Flow::Next,
+        }
+    }
+}
+
+impl std::fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("middlewares", &self.middlewares.len())
+            .field("routes", &self.routes.len())
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock;
+    use serde_json::from_str;
+    use std::sync::Mutex;
+
+    fn text_item(content: &str) -> MsgItem {
+        let str = format!(
+            r#"{{
+                "msgid": "MSG_ID",
+                "open_kfid": "OPEN_KFID",
+                "external_userid": "EXTERNAL_USERID",
+                "send_time": 1615478585,
+                "origin": 3,
+                "msgtype": "text",
+                "text": {{ "content": "{content}" }}
+            }}"#
+        );
+        from_str(&str).unwrap()
+    }
+
+    fn event_item(event_type: &str) -> MsgItem {
+        let str = format!(
+            r#"{{
+                "msgid": "MSG_ID",
+                "send_time": 1615478585,
+                "origin": 4,
+                "msgtype": "event",
+                "event": {{
+                    "event_type": "{event_type}",
+                    "open_kfid": "OPEN_KFID",
+                    "external_userid": "EXTERNAL_USERID"
+                }}
+            }}"#
+        );
+        from_str(&str).unwrap()
+    }
+
+    fn recorder(
+        log: &Arc<Mutex<Vec<&'static str>>>,
+        name: &'static str,
+        flow: Flow,
+    ) -> impl Fn(Context) -> BoxFuture<'static, Flow> + Send + Sync + 'static {
+        let log = log.clone();
+        move |_| {
+            log.lock().unwrap().push(name);
+            Box::pin(async move { flow })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let dispatcher = Dispatcher::new()
+            .on_text_matching(
+                Regex::new("^订单").unwrap(),
+                recorder(&log, "order", Flow::Next),
+            )
+            .on_text(recorder(&log, "text", Flow::Stop))
+            .on_event(KfEvent::EnterSession, recorder(&log, "enter", Flow::Stop))
+            .fallback(recorder(&log, "fallback", Flow::Stop));
+        let client = mock::client();
+
+        dispatcher.dispatch(&client, text_item("订单查询")).await;
+        assert_eq!(*log.lock().unwrap(), ["order", "text"]);
+
+        log.lock().unwrap().clear();
+        dispatcher.dispatch(&client, text_item("你好")).await;
+        assert_eq!(*log.lock().unwrap(), ["text"]);
+
+        log.lock().unwrap().clear();
+        dispatcher
+            .dispatch(&client, event_item("enter_session"))
+            .await;
+        assert_eq!(*log.lock().unwrap(), ["enter"]);
+
+        log.lock().unwrap().clear();
+        let flow = dispatcher
+            .dispatch(&client, event_item("msg_send_fail"))
+            .await;
+        assert_eq!(*log.lock().unwrap(), ["fallback"]);
+        assert_eq!(flow, Flow::Stop);
+    }
+
+    #[test]
+    fn test_unknown_event() {
+        let item = event_item("some_new_event");
+        assert_eq!(item.message.event_type(), Some(KfEvent::Unknown));
+    }
+}
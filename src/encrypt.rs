@@ -1,3 +1,61 @@
-fn add(a: i8, b: i8) -> i8 {
-    a + b
+use aes::cipher::{block_padding::NoPadding, BlockEncryptMut, KeyIvInit};
+use base64::{engine::general_purpose, Engine as _};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::decrypt::{decrypt_ase_key, DecryptErr};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+
+/// 补位的块大小
+const BLOCK_SIZE: usize = 32;
+
+/// 加密消息，`random`为16字节的随机串
+pub fn encrypt_msg(
+    msg: &str,
+    receiveid: &str,
+    encode_ase_key: &str,
+    random: &[u8; 16],
+) -> Result<String, DecryptErr> {
+    let ase_key = decrypt_ase_key(encode_ase_key)?;
+    let mut content = random.to_vec();
+    content.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    content.extend_from_slice(msg.as_bytes());
+    content.extend_from_slice(receiveid.as_bytes());
+    let pad = BLOCK_SIZE - content.len() % BLOCK_SIZE;
+    content.extend(std::iter::repeat_n(pad as u8, pad));
+    let iv = &ase_key[..16];
+    let key = &ase_key[..32];
+    let cipher = Aes256CbcEnc::new_from_slices(key, iv)?;
+    let mut buffer = vec![0u8; content.len()];
+    let result = cipher
+        .encrypt_padded_b2b_mut::<NoPadding>(&content, &mut buffer)
+        .map_err(|e| DecryptErr::Std(e.to_string()))?;
+    Ok(general_purpose::STANDARD.encode(result))
+}
+
+/// 生成16字节的随机串
+pub fn random_bytes() -> [u8; 16] {
+    let mut result = [0u8; 16];
+    for chunk in result.chunks_mut(8) {
+        let value = RandomState::new().build_hasher().finish();
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decrypt::decrypt_msg;
+
+    #[test]
+    fn test_encrypt_msg() {
+        let encode_ase_key = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+        let msg = "<xml><ToUserName><![CDATA[wx5823bf96d3bd56c7]]></ToUserName></xml>";
+        let result = encrypt_msg(msg, "wx5823bf96d3bd56c7", encode_ase_key, &random_bytes());
+        let decrypt = decrypt_msg(&result.unwrap(), encode_ase_key).unwrap();
+        assert_eq!(decrypt.msg, msg);
+        assert_eq!(decrypt.receiveid, "wx5823bf96d3bd56c7");
+    }
 }
@@ -0,0 +1,144 @@
+use serde::de::DeserializeOwned;
+
+use crate::transport::{HttpRequest, Method};
+
+/// 在路径后追加查询参数，参数值按`application/x-www-form-urlencoded`编码
+pub(crate) fn with_query(path: &str, params: &[(&str, &str)]) -> String {
+    let query = serde_urlencoded::to_string(params).expect("string pairs are always encodable");
+    let sep = if path.contains('?') { '&' } else { '?' };
+    format!("{path}{sep}{query}")
+}
+
+/// 与HTTP客户端无关的接口描述
+///
+/// 描述接口的请求方法、路径、请求体以及如何解析响应，可用于批量请求、回放和自定义执行器。
+pub trait Endpoint {
+    type Response: DeserializeOwned;
+
+    fn method(&self) -> Method {
+        Method::Post
+    }
+
+    /// 相对于`https://qyapi.weixin.qq.com/cgi-bin`的路径
+    fn path(&self) -> String;
+
+    /// JSON格式的请求体
+    fn body(&self) -> Option<Vec<u8>>;
+
+    /// 是否需要携带access_token
+    fn authorized(&self) -> bool {
+        true
+    }
+
+    /// 重复执行是否安全，非幂等的接口只在请求确定未被处理时重试
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    /// 请求所属的客服账号，用于按客服账号限流
+    fn open_kfid(&self) -> Option<&str> {
+        None
+    }
+
+    /// 解析响应
+    fn parse_response(bytes: &[u8]) -> serde_json::Result<Self::Response> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// 生成请求
+    fn to_request(&self, base_url: &str, token: &str) -> HttpRequest {
+        let url = format!("{base_url}{}", self.path());
+        let url = if self.authorized() {
+            with_query(&url, &[("access_token", token)])
+        } else {
+            url
+        };
+        let body = self.body();
+        let headers = match body {
+            Some(_) => vec![("content-type".to_string(), "application/json".to_string())],
+            None => vec![],
+        };
+        HttpRequest {
+            method: self.method(),
+            url,
+            headers,
+            body: body.unwrap_or_default(),
+        }
+    }
+}
+
+impl<E: Endpoint + ?Sized> Endpoint for &E {
+    type Response = E::Response;
+
+    fn method(&self) -> Method {
+        (**self).method()
+    }
+
+    fn path(&self) -> String {
+        (**self).path()
+    }
+
+    fn body(&self) -> Option<Vec<u8>> {
+        (**self).body()
+    }
+
+    fn authorized(&self) -> bool {
+        (**self).authorized()
+    }
+
+    fn idempotent(&self) -> bool {
+        (**self).idempotent()
+    }
+
+    fn open_kfid(&self) -> Option<&str> {
+        (**self).open_kfid()
+    }
+
+    fn parse_response(bytes: &[u8]) -> serde_json::Result<Self::Response> {
+        E::parse_response(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::GetMedia;
+    use crate::servicer::ListServicer;
+    use crate::GetToken;
+    use std::collections::HashMap;
+
+    const SPECIAL: &str = "a&b+c#d=e f%";
+
+    fn query(req: &HttpRequest) -> HashMap<String, String> {
+        let (_, query) = req.url.split_once('?').unwrap();
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    #[test]
+    fn test_query_encoding() {
+        let base_url = "https://qyapi.weixin.qq.com/cgi-bin";
+        let req = GetToken::new(SPECIAL, SPECIAL).to_request(base_url, "");
+        assert_eq!(
+            req.url,
+            format!("{base_url}/gettoken?corpid=a%26b%2Bc%23d%3De+f%25&corpsecret=a%26b%2Bc%23d%3De+f%25")
+        );
+        let params = query(&req);
+        assert_eq!(
+            (params["corpid"].as_str(), params["corpsecret"].as_str()),
+            (SPECIAL, SPECIAL)
+        );
+
+        let req = ListServicer::new(SPECIAL).to_request(base_url, SPECIAL);
+        let params = query(&req);
+        assert_eq!(params["open_kfid"], SPECIAL);
+        assert_eq!(params["access_token"], SPECIAL);
+        assert_eq!(params.len(), 2);
+
+        let req = GetMedia::new(SPECIAL).to_request(base_url, SPECIAL);
+        let params = query(&req);
+        assert_eq!(
+            (params["media_id"].as_str(), params["access_token"].as_str()),
+            (SPECIAL, SPECIAL)
+        );
+    }
+}
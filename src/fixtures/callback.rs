@@ -0,0 +1,77 @@
+use super::{CORP_ID, OPEN_KFID};
+use crate::callback::CallbackConfig;
+use crate::parse::WeiXinCallbackRes;
+use crate::verify::{VerifyErr, WeiXinCallbackParam};
+
+/// 示例回调Token，与[`ENCRYPTED_ECHOSTR`]配套
+pub const CALLBACK_TOKEN: &str = "QDG6eK";
+/// 示例EncodingAESKey，与[`ENCRYPTED_ECHOSTR`]配套
+pub const ENCODING_AES_KEY: &str = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+/// 示例回调的时间戳
+pub const TIMESTAMP: &str = "1409659813";
+/// 示例回调的随机数
+pub const NONCE: &str = "1372623149";
+/// 企业微信文档中验证URL有效性的加密echostr
+pub const ENCRYPTED_ECHOSTR: &str = "RypEvHKD8QQKFhvQ6QleEB4J58tiPdvo+rtK1I9qca6aM/wvqnLSV5zEPeusUiX5L5X/0lWfrf0QADHHhGd3QczcdCUpj911L3vg3W/sYYvuJTs3TUUkSUXxaccAS0qhxchrRYt66wiSpGLYL42aM6A8dTT+6k4aSknmPj48kzJs8qLjvd4Xgpue06DOdnLxAUHzM6+kDZ+HMZfJYuR+LtwGc2hgf5gsijff0ekUNXZiqATP7PF5mZxZ3Izoun1s4zG4LUMnvw2r+KqCKIw+3IQH03v+BCA9nMELNqbSf6tiWSrXJB3LAVGUcallcrw8V2t9EL4EhzJWrQUax5wLVMNS0+rUPA3k22Ncx4XXZS9o0MBH27Bo6BpNelZpS+/uh9KsNlY6bHCmJU9p8g7m3fVKn28H3KDYA5Pl/T8Z1ptDAVe0lXdQ2YoyyH2uyPIGHBZZIs2pDBS8R07+qN+E7Q==";
+/// [`ENCRYPTED_ECHOSTR`]的签名
+pub const ECHOSTR_SIGNATURE: &str = "477715d11cdb4164915debcba66cb864d751f3e6";
+/// 回调事件中用于拉取消息的示例token
+pub const SYNC_TOKEN: &str = "ENCApHxnGDNAVNY4AaSJKj4Tb5mwsEMzxhFmHVGcra996NR";
+
+/// 示例回调配置
+pub fn callback_config() -> CallbackConfig {
+    CallbackConfig::new(CALLBACK_TOKEN, ENCODING_AES_KEY, CORP_ID)
+}
+
+/// 验证URL有效性请求的参数
+pub fn verify_params() -> WeiXinCallbackParam {
+    WeiXinCallbackParam {
+        timestamp: TIMESTAMP.to_string(),
+        nonce: NONCE.to_string(),
+        echostr: ENCRYPTED_ECHOSTR.to_string(),
+        msg_signature: ECHOSTR_SIGNATURE.to_string(),
+    }
+}
+
+/// 客服消息或事件回调的明文
+pub fn callback_event() -> WeiXinCallbackRes {
+    WeiXinCallbackRes {
+        create_time: TIMESTAMP.to_string(),
+        ..WeiXinCallbackRes::new(CORP_ID, SYNC_TOKEN, OPEN_KFID)
+    }
+}
+
+/// 客服消息或事件回调的明文XML
+pub fn callback_xml() -> String {
+    callback_event().to_xml()
+}
+
+/// 加密的回调请求，返回请求体和URL参数
+///
+/// 加密使用随机数，每次生成的请求体不同，但都能用[`callback_config`]解密为[`callback_event`]。
+pub fn encrypted_callback() -> Result<(String, WeiXinCallbackParam), VerifyErr> {
+    callback_config().encrypt(CORP_ID, &callback_xml(), TIMESTAMP, NONCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_callback_xml;
+
+    #[test]
+    fn test_callback_round_trip() {
+        let event = parse_callback_xml(&callback_xml()).unwrap();
+        assert_eq!(event, callback_event());
+        assert_eq!(event.to_xml(), callback_xml());
+
+        let (body, params) = encrypted_callback().unwrap();
+        assert_eq!(params.timestamp, TIMESTAMP);
+        assert_eq!(callback_config().decrypt(&params, &body), Ok(event));
+    }
+
+    #[test]
+    fn test_verify() {
+        let echo = callback_config().verify(&verify_params()).unwrap();
+        assert!(echo.starts_with(&format!("<xml><ToUserName><![CDATA[{CORP_ID}]]>")));
+    }
+}
@@ -0,0 +1,437 @@
+use serde_json::{json, Value};
+
+use crate::msg_res::{KfEvent, MsgItem, MsgOrigin};
+
+mod callback;
+mod res;
+
+pub use callback::*;
+pub use res::*;
+
+/// 示例企业ID，与[`ENCRYPTED_ECHOSTR`]中的receiveid一致
+pub const CORP_ID: &str = "wx5823bf96d3bd56c7";
+/// 示例客服账号
+pub const OPEN_KFID: &str = "wkAJ2GCAAASSm4_FhToWMFea0xAFfd3Q";
+/// 示例微信客户
+pub const EXTERNAL_USERID: &str = "wmAJ2GCAAAme1XQRC-NI-q0_ZM9ukoAw";
+/// 示例接待人员
+pub const SERVICER_USERID: &str = "zhangsan";
+/// 示例消息ID
+pub const MSGID: &str = "from_msgid_4622416642169452483";
+/// 示例消息发送时间
+pub const SEND_TIME: u64 = 1615478585;
+/// 示例媒体文件ID
+pub const MEDIA_ID: &str = "2iSLeVyqzk4eX0IB5kTi9Ljfa2rt9dwfq5WKRQ4Nvvgw";
+
+/// 所有消息类型
+pub const MSG_TYPES: &[&str] = &[
+    "text",
+    "image",
+    "voice",
+    "video",
+    "file",
+    "location",
+    "link",
+    "business_card",
+    "miniprogram",
+    "msgmenu",
+    "channels_shop_product",
+    "channels_shop_order",
+];
+
+/// 所有事件类型
+pub const EVENTS: &[KfEvent] = &[
+    KfEvent::EnterSession,
+    KfEvent::MsgSendFail,
+    KfEvent::ServicerStatusChange,
+    KfEvent::SessionStatusChange,
+    KfEvent::UserRecallMsg,
+    KfEvent::ServicerRecallMsg,
+    KfEvent::RejectCustomerMsgSwitchChange,
+];
+
+/// `sync_msg`返回的单条消息或事件的构建器
+///
+/// 各构造函数生成与接口文档一致的标准消息，可再通过setter修改个别字段：
+///
+/// ```ignore
+/// let item = ItemBuilder::text("你好").external_userid("USER").send_time(0).build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ItemBuilder {
+    item: Value,
+}
+
+impl ItemBuilder {
+    /// 微信客户发送的消息
+    fn message(msgtype: &str, content: Value) -> Self {
+        let mut item = json!({
+            "msgid": MSGID,
+            "open_kfid": OPEN_KFID,
+            "external_userid": EXTERNAL_USERID,
+            "send_time": SEND_TIME,
+            "origin": MsgOrigin::WeiXinCustomer,
+            "msgtype": msgtype,
+        });
+        item[msgtype] = content;
+        Self { item }
+    }
+
+    /// 指定类型的标准消息，`msgtype`不在[`MSG_TYPES`]中时返回`None`
+    pub fn of_type(msgtype: &str) -> Option<Self> {
+        let builder = match msgtype {
+            "text" => Self::text("你好"),
+            "image" => Self::media("image"),
+            "voice" => Self::media("voice"),
+            "video" => Self::media("video"),
+            "file" => Self::media("file"),
+            "location" => Self::location(),
+            "link" => Self::link(),
+            "business_card" => Self::business_card(),
+            "miniprogram" => Self::miniprogram(),
+            "msgmenu" => Self::msgmenu(),
+            "channels_shop_product" => Self::channels_shop_product(),
+            "channels_shop_order" => Self::channels_shop_order(),
+            _ => return None,
+        };
+        Some(builder)
+    }
+
+    /// 所有类型的标准消息
+    pub fn all_messages() -> Vec<Self> {
+        MSG_TYPES.iter().filter_map(|t| Self::of_type(t)).collect()
+    }
+
+    /// 所有类型的标准事件
+    pub fn all_events() -> Vec<Self> {
+        EVENTS.iter().map(|event| Self::event(*event)).collect()
+    }
+
+    /// 文本消息
+    pub fn text(content: &str) -> Self {
+        Self::message("text", json!({ "content": content }))
+    }
+
+    /// 客户点击回复菜单产生的文本消息
+    pub fn menu_click(menu_id: &str, content: &str) -> Self {
+        Self::message("text", json!({ "menu_id": menu_id, "content": content }))
+    }
+
+    /// 图片、语音、视频或文件消息
+    pub fn media(msgtype: &str) -> Self {
+        Self::message(msgtype, json!({ "media_id": MEDIA_ID }))
+    }
+
+    /// 位置消息
+    pub fn location() -> Self {
+        Self::message(
+            "location",
+            json!({
+                "latitude": 23.125,
+                "longitude": 113.3125,
+                "name": "广州国际媒体港(广州市海珠区)",
+                "address": "广东省广州市海珠区滨江东路",
+            }),
+        )
+    }
+
+    /// 链接消息
+    pub fn link() -> Self {
+        Self::message(
+            "link",
+            json!({
+                "title": "TITLE",
+                "desc": "DESC",
+                "url": "https://work.weixin.qq.com",
+                "pic_url": "https://work.weixin.qq.com/pic.png",
+            }),
+        )
+    }
+
+    /// 名片消息
+    pub fn business_card() -> Self {
+        Self::message("business_card", json!({ "userid": SERVICER_USERID }))
+    }
+
+    /// 小程序消息
+    pub fn miniprogram() -> Self {
+        Self::message(
+            "miniprogram",
+            json!({
+                "title": "TITLE",
+                "appid": "wx8bd80126147dfAAA",
+                "pagepath": "pages/index.html",
+                "thumb_media_id": MEDIA_ID,
+            }),
+        )
+    }
+
+    /// 菜单消息
+    pub fn msgmenu() -> Self {
+        Self::message(
+            "msgmenu",
+            json!({
+                "head_content": "您对本次服务是否满意呢? ",
+                "list": [
+                    { "type": "click", "click": { "id": "101", "content": "满意" } },
+                    { "type": "click", "click": { "id": "102", "content": "不满意" } },
+                ],
+                "tail_content": "欢迎再次光临",
+            }),
+        )
+    }
+
+    /// 视频号商品消息
+    pub fn channels_shop_product() -> Self {
+        Self::message(
+            "channels_shop_product",
+            json!({
+                "product_id": "10000051113021",
+                "head_image": "https://mmecimage.cn/p/head.png",
+                "title": "TITLE",
+                "sales_price": "100.00",
+                "shop_nickname": "SHOP_NICKNAME",
+                "shop_head_image": "https://mmecimage.cn/p/shop.png",
+            }),
+        )
+    }
+
+    /// 视频号订单消息
+    pub fn channels_shop_order() -> Self {
+        Self::message(
+            "channels_shop_order",
+            json!({
+                "order_id": "3705115058471208928",
+                "product_titles": "TITLE1,TITLE2",
+                "price_wording": "100.00",
+                "state": "待发货",
+                "image_url": "https://mmecimage.cn/p/order.png",
+                "shop_nickname": "SHOP_NICKNAME",
+            }),
+        )
+    }
+
+    /// 指定类型的标准事件，未知事件类型生成只带`event_type`的事件
+    pub fn event(event: KfEvent) -> Self {
+        let body = match event {
+            KfEvent::EnterSession => json!({
+                "event_type": "enter_session",
+                "open_kfid": OPEN_KFID,
+                "external_userid": EXTERNAL_USERID,
+                "scene": "123",
+                "scene_param": "abc",
+                "welcome_code": "aaaaaa",
+                "wechat_channels": { "nickname": "进入会话的视频号名称", "scene": 1 },
+            }),
+            KfEvent::MsgSendFail => json!({
+                "event_type": "msg_send_fail",
+                "open_kfid": OPEN_KFID,
+                "external_userid": EXTERNAL_USERID,
+                "fail_msgid": "FAIL_MSGID",
+                "fail_type": 4,
+            }),
+            KfEvent::ServicerStatusChange => json!({
+                "event_type": "servicer_status_change",
+                "servicer_userid": SERVICER_USERID,
+                "status": 1,
+                "stop_type": 1,
+                "open_kfid": OPEN_KFID,
+            }),
+            KfEvent::SessionStatusChange => json!({
+                "event_type": "session_status_change",
+                "open_kfid": OPEN_KFID,
+                "external_userid": EXTERNAL_USERID,
+                "change_type": 1,
+                "old_servicer_userid": "OLD_SERVICER_USERID",
+                "new_servicer_userid": "NEW_SERVICER_USERID",
+                "msg_code": "MSG_CODE",
+            }),
+            KfEvent::UserRecallMsg => json!({
+                "event_type": "user_recall_msg",
+                "open_kfid": OPEN_KFID,
+                "external_userid": EXTERNAL_USERID,
+                "recall_msgid": "RECALL_MSGID",
+            }),
+            KfEvent::ServicerRecallMsg => json!({
+                "event_type": "servicer_recall_msg",
+                "open_kfid": OPEN_KFID,
+                "external_userid": EXTERNAL_USERID,
+                "recall_msgid": "RECALL_MSGID",
+                "servicer_userid": SERVICER_USERID,
+            }),
+            KfEvent::RejectCustomerMsgSwitchChange => json!({
+                "event_type": "reject_customer_msg_switch_change",
+                "servicer_userid": SERVICER_USERID,
+                "open_kfid": OPEN_KFID,
+                "external_userid": EXTERNAL_USERID,
+                "reject_switch": 1,
+            }),
+            KfEvent::Unknown => json!({ "event_type": "unknown" }),
+        };
+        let item = json!({
+            "msgid": MSGID,
+            "send_time": SEND_TIME,
+            "origin": MsgOrigin::System,
+            "msgtype": "event",
+            "event": body,
+        });
+        Self { item }
+    }
+
+    fn is_event(&self) -> bool {
+        self.item["msgtype"] == "event"
+    }
+
+    /// 消息ID
+    pub fn msgid(mut self, msgid: &str) -> Self {
+        self.item["msgid"] = json!(msgid);
+        self
+    }
+
+    /// 发送时间
+    pub fn send_time(mut self, send_time: u64) -> Self {
+        self.item["send_time"] = json!(send_time);
+        self
+    }
+
+    /// 消息来源
+    pub fn origin(mut self, origin: MsgOrigin) -> Self {
+        self.item["origin"] = json!(origin);
+        self
+    }
+
+    /// 客服账号，事件中修改`event.open_kfid`
+    pub fn open_kfid(self, open_kfid: &str) -> Self {
+        self.set_id("open_kfid", open_kfid)
+    }
+
+    /// 微信客户，事件中修改`event.external_userid`
+    pub fn external_userid(self, external_userid: &str) -> Self {
+        self.set_id("external_userid", external_userid)
+    }
+
+    /// 接待人员，通常用于客服发送的消息
+    pub fn servicer_userid(mut self, servicer_userid: &str) -> Self {
+        self.item["servicer_userid"] = json!(servicer_userid);
+        self
+    }
+
+    fn set_id(mut self, key: &str, value: &str) -> Self {
+        if self.is_event() {
+            self.item["event"][key] = json!(value);
+        } else {
+            self.item[key] = json!(value);
+        }
+        self
+    }
+
+    /// 修改消息内容中的字段，事件修改`event`中的字段；`value`为`null`时删除该字段
+    pub fn field(mut self, key: &str, value: impl Into<Value>) -> Self {
+        let msgtype = self.item["msgtype"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if let Some(content) = self.item[msgtype.as_str()].as_object_mut() {
+            match value.into() {
+                Value::Null => content.remove(key),
+                value => content.insert(key.to_string(), value),
+            };
+        }
+        self
+    }
+
+    /// 生成JSON
+    pub fn to_json(&self) -> Value {
+        self.item.clone()
+    }
+
+    /// 生成消息，字段被修改得不符合格式时panic
+    pub fn build(&self) -> MsgItem {
+        serde_json::from_value(self.to_json()).expect("fixture should deserialize to MsgItem")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg_res::Message;
+    use serde_json::to_value;
+
+    fn menu_id(item: &MsgItem) -> Option<&str> {
+        match &item.message {
+            Message::Text { menu_id, .. } => menu_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let builders = ItemBuilder::all_messages();
+        assert_eq!(builders.len(), MSG_TYPES.len());
+        for builder in builders.iter().chain(&ItemBuilder::all_events()) {
+            let fixture = builder.to_json();
+            let item = builder.build();
+            assert_eq!(item.msgtype, fixture["msgtype"]);
+            assert!(!matches!(item.message.event_type(), Some(KfEvent::Unknown)));
+
+            let value = to_value(&item).unwrap();
+            assert_eq!(value, fixture, "{}", item.msgtype);
+            let again: MsgItem = serde_json::from_value(value.clone()).unwrap();
+            assert_eq!(to_value(&again).unwrap(), fixture, "{}", item.msgtype);
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        for (event, builder) in EVENTS.iter().zip(ItemBuilder::all_events()) {
+            let item = builder.build();
+            assert_eq!(item.origin, MsgOrigin::System);
+            assert_eq!(item.message.event_type(), Some(*event));
+        }
+        let item = ItemBuilder::event(KfEvent::ServicerStatusChange).build();
+        let Message::Event {
+            external_userid, ..
+        } = item.message
+        else {
+            panic!("not an event");
+        };
+        assert_eq!(external_userid, "");
+    }
+
+    #[test]
+    fn test_builder() {
+        let item = ItemBuilder::menu_click("101", "满意")
+            .msgid("MSG")
+            .external_userid("USER")
+            .send_time(1)
+            .build();
+        assert_eq!(menu_id(&item), Some("101"));
+        assert_eq!(item.external_userid.as_deref(), Some("USER"));
+        assert_eq!((item.msgid.as_str(), item.send_time), ("MSG", 1));
+
+        let item = ItemBuilder::text("你好").field("menu_id", "102").build();
+        assert_eq!(menu_id(&item), Some("102"));
+        let item = ItemBuilder::menu_click("101", "满意")
+            .field("menu_id", Value::Null)
+            .build();
+        assert_eq!(menu_id(&item), None);
+
+        let item = ItemBuilder::event(KfEvent::EnterSession)
+            .external_userid("USER")
+            .field("scene", "vip")
+            .build();
+        let Message::Event {
+            external_userid,
+            scene,
+            ..
+        } = item.message
+        else {
+            panic!("not an event");
+        };
+        assert_eq!(
+            (external_userid.as_str(), scene.as_deref()),
+            ("USER", Some("vip"))
+        );
+        assert!(ItemBuilder::of_type("unknown").is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+
+use super::{EXTERNAL_USERID, MSGID, OPEN_KFID, SERVICER_USERID};
+use crate::retry::SYSTEM_BUSY_ERRCODE;
+
+/// corpid或corpsecret错误
+pub const INVALID_SECRET: i32 = 40001;
+/// access_token无效
+pub const INVALID_TOKEN: i32 = 40014;
+/// 参数错误
+pub const INVALID_PARAMETER: i32 = 40058;
+/// access_token已过期
+pub const TOKEN_EXPIRED: i32 = 42001;
+/// 接口调用超过频率限制
+pub const API_FREQ_OUT_OF_LIMIT: i32 = crate::rate_limit::API_FREQ_OUT_OF_LIMIT_ERRCODE;
+/// 模拟服务在msgid重复时返回的错误码
+///
+/// 这是占位值：接口文档没有列出msgid重复对应的错误码，真实接口的返回可能不同，
+/// 对接真实接口时应按实际返回设置[`Client::duplicate_msgid_errcode`](crate::Client::duplicate_msgid_errcode)。
+pub const DUPLICATE_MSGID: i32 = 95018;
+/// 拉取消息时的token无效
+pub const INVALID_MSG_TOKEN: i32 = 95007;
+
+/// 常见的接口错误码
+pub const COMMON_ERRCODES: &[i32] = &[
+    SYSTEM_BUSY_ERRCODE,
+    INVALID_SECRET,
+    INVALID_TOKEN,
+    INVALID_PARAMETER,
+    TOKEN_EXPIRED,
+    API_FREQ_OUT_OF_LIMIT,
+    DUPLICATE_MSGID,
+    INVALID_MSG_TOKEN,
+];
+
+/// 错误响应
+pub fn error(errcode: i32) -> Value {
+    let errmsg = match errcode {
+        SYSTEM_BUSY_ERRCODE => "system busy",
+        INVALID_SECRET => "invalid credential",
+        INVALID_TOKEN => "invalid access_token",
+        INVALID_PARAMETER => "invalid parameter",
+        TOKEN_EXPIRED => "access_token expired",
+        API_FREQ_OUT_OF_LIMIT => "api freq out of limit",
+        DUPLICATE_MSGID => "msgid duplicated",
+        INVALID_MSG_TOKEN => "invalid msg token",
+        _ => "error",
+    };
+    json!({ "errcode": errcode, "errmsg": errmsg })
+}
+
+/// 成功响应，在`extra`的基础上加上`errcode`和`errmsg`
+pub fn ok(mut extra: Value) -> Value {
+    extra["errcode"] = json!(0);
+    extra["errmsg"] = json!("ok");
+    extra
+}
+
+/// 获取access_token
+pub fn access_token() -> Value {
+    ok(json!({ "access_token": "ACCESS_TOKEN", "expires_in": 7200 }))
+}
+
+/// 添加客服账号
+pub fn account_add() -> Value {
+    ok(json!({ "open_kfid": OPEN_KFID }))
+}
+
+/// 获取客服账号列表
+pub fn account_list() -> Value {
+    ok(json!({
+        "account_list": [
+            {
+                "open_kfid": OPEN_KFID,
+                "name": "NAME",
+                "avatar": "https://wework.qpic.cn/wwhead/duc2TvpEgSSjibPZlNR6chpx9W3dtd9Ogp8XEmSNKGa6uufMWn2239HUPuwIFoYYZ7Ph580FPvo8/0",
+                "manage_privilege": false,
+            }
+        ]
+    }))
+}
+
+/// 获取客服账号链接
+pub fn account_link() -> Value {
+    ok(json!({ "url": "https://work.weixin.qq.com/kf/kfcbf8f8d07ac7215f?enc_scene=ENCGFSDF567DF" }))
+}
+
+/// 不返回额外字段的接口，如删除、修改客服账号和撤回消息
+pub fn simple() -> Value {
+    ok(json!({}))
+}
+
+/// 拉取消息，`msg_list`可使用[`ItemBuilder`](super::ItemBuilder)生成
+pub fn sync_msg(msg_list: Vec<Value>, next_cursor: &str, has_more: bool) -> Value {
+    ok(json!({
+        "next_cursor": next_cursor,
+        "has_more": u8::from(has_more),
+        "msg_list": msg_list,
+    }))
+}
+
+/// 发送消息或事件响应消息
+pub fn send_msg() -> Value {
+    ok(json!({ "msgid": MSGID }))
+}
+
+/// 获取会话状态
+pub fn service_state() -> Value {
+    ok(json!({ "service_state": 3, "servicer_userid": SERVICER_USERID }))
+}
+
+/// 变更会话状态
+pub fn trans_service_state() -> Value {
+    ok(json!({ "msg_code": "MSG_CODE" }))
+}
+
+/// 发送消息的请求体
+pub fn send_msg_request() -> Value {
+    json!({
+        "touser": EXTERNAL_USERID,
+        "open_kfid": OPEN_KFID,
+        "msgid": "MSGID",
+        "msgtype": "text",
+        "text": { "content": "你购买的物品已发货，可点击链接查看物流状态http://work.weixin.qq.com/xxxxxx" },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{AccountLinkRes, AddRes, ListRes, SimpleRes};
+    use crate::fixtures::ItemBuilder;
+    use crate::receive::{MoreMsg, MsgRes};
+    use crate::send::{MessageRes, OutgoingMessage};
+    use crate::service_state::{ServiceState, ServiceStateRes, TransServiceStateRes};
+    use crate::welcome::WelcomeRes;
+    use crate::AccessTokenRes;
+    use serde::de::DeserializeOwned;
+    use serde_json::{from_value, to_value};
+
+    /// 成功响应和所有常见错误响应都能解析
+    fn parse_all<T: DeserializeOwned>(success: Value) -> T {
+        for errcode in COMMON_ERRCODES {
+            assert!(
+                from_value::<T>(error(*errcode)).is_ok(),
+                "errcode {errcode}"
+            );
+        }
+        from_value(success).unwrap()
+    }
+
+    #[test]
+    fn test_responses() {
+        let res: AccessTokenRes = parse_all(access_token());
+        assert_eq!(
+            (res.access_token.expose().as_str(), res.expires_in),
+            ("ACCESS_TOKEN", 7200)
+        );
+        let res: AddRes = parse_all(account_add());
+        assert_eq!(res.open_kfid, OPEN_KFID);
+        let res: ListRes = parse_all(account_list());
+        assert_eq!(res.account_list[0].open_kfid, OPEN_KFID);
+        let res: AccountLinkRes = parse_all(account_link());
+        assert!(res.url.starts_with("https://work.weixin.qq.com/kf/"));
+        let res: SimpleRes = parse_all(simple());
+        assert_eq!(res.errcode, 0);
+        let res: MessageRes = parse_all(send_msg());
+        assert_eq!(res.msgid, MSGID);
+        let res: WelcomeRes = parse_all(send_msg());
+        assert_eq!(res.msgid, MSGID);
+        let res: ServiceStateRes = parse_all(service_state());
+        assert_eq!(res.service_state, ServiceState::Servicer);
+        let res: TransServiceStateRes = parse_all(trans_service_state());
+        assert_eq!(res.msg_code.as_deref(), Some("MSG_CODE"));
+
+        let msg_list = ItemBuilder::all_messages()
+            .iter()
+            .chain(&ItemBuilder::all_events())
+            .map(ItemBuilder::to_json)
+            .collect();
+        let res: MsgRes = parse_all(sync_msg(msg_list, "4gw7MepFLfgF2VC5npN", true));
+        assert_eq!(res.has_more, MoreMsg::Yes);
+        assert_eq!(res.next_cursor, "4gw7MepFLfgF2VC5npN");
+    }
+
+    #[test]
+    fn test_send_msg_request() {
+        let fixture = send_msg_request();
+        let message = OutgoingMessage::text(fixture["text"]["content"].as_str().unwrap())
+            .to(EXTERNAL_USERID)
+            .from_kf(OPEN_KFID)
+            .msgid("MSGID")
+            .build()
+            .unwrap();
+        assert_eq!(to_value(&message).unwrap(), fixture);
+    }
+}
@@ -1,44 +1,88 @@
+#[macro_use]
+mod macros;
+
 /// 客户账号管理
 pub mod account;
+/// actix-web回调提取器
+#[cfg(feature = "actix")]
+pub mod actix;
+/// axum回调路由
+#[cfg(feature = "axum")]
+pub mod axum;
+/// 回调模块
+pub mod callback;
+/// 客户端
+mod client;
 /// 常量
 mod constant;
 /// 解密模块
 pub mod decrypt;
-// 加密模块
-mod encrypt;
+/// 消息分发
+pub mod dispatcher;
+/// 加密模块
+pub mod encrypt;
+/// 与HTTP客户端无关的接口描述
+pub mod endpoint;
+/// 符合接口文档格式的消息、事件、响应和回调样例
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+/// 临时素材
+pub mod media;
 /// 客服消息
 mod message;
+/// 指标收集
+pub mod metrics;
+/// 消息处理中间件
+pub mod middleware;
 mod msg_res;
 /// 解析模块
 mod parse;
+/// 客户端限流
+pub mod rate_limit;
+/// 请求模块
+#[cfg(feature = "reqwest")]
+mod request;
+/// 重试策略
+pub mod retry;
+/// 敏感值的包装和脱敏
+pub mod secret;
+/// 接待人员管理
+pub mod servicer;
 /// 签名模块
 pub mod signature;
+/// 消息同步
+pub mod sync;
+/// 本地模拟的客服接口服务，用于集成测试
+#[cfg(feature = "testing")]
+pub mod testing;
+/// 访问令牌
+mod token;
+/// HTTP传输层
+pub mod transport;
 /// 验证模块
 mod verify;
+/// 48小时发送窗口
+pub mod window;
 
-use serde::Deserialize;
-
+pub use client::*;
 pub use message::*;
 pub use msg_res::*;
-pub use parse::parse_callback_xml;
+pub use parse::{parse_callback_xml, WeiXinCallbackRes};
+#[cfg(feature = "reqwest")]
+pub use token::access_token;
+pub use token::{AccessTokenRes, GetToken};
 pub use verify::*;
 
-#[derive(Debug, Deserialize)]
-pub struct AccessTokenRes {
-    pub errcode: i32,
-    pub errmsg: String,
-    pub access_token: String,
-    pub expires_in: i32,
-}
-
-pub async fn access_token(id: &str, secret: &str) -> Result<AccessTokenRes, reqwest::Error> {
-    let url = format!(
-        "https://qyapi.weixin.qq.com/cgi-bin/gettoken?corpid={}&corpsecret={}",
-        id, secret
-    );
-    reqwest::get(url)
-        .await
-        .unwrap()
-        .json::<AccessTokenRes>()
-        .await
+/// 同步接口，与异步接口一一对应，需要启用`blocking`特性
+#[cfg(feature = "blocking")]
+pub mod blocking {
+    pub use crate::account::blocking as account;
+    pub use crate::media::blocking as media;
+    pub use crate::recall::blocking as recall;
+    pub use crate::receive::blocking as receive;
+    pub use crate::send::blocking as send;
+    pub use crate::service_state::blocking as service_state;
+    pub use crate::servicer::blocking as servicer;
+    pub use crate::token::blocking::access_token;
+    pub use crate::welcome::blocking as welcome;
 }
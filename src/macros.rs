@@ -0,0 +1,70 @@
+/// 定义接口，同时生成基于reqwest的异步函数和`blocking`子模块中的同步函数
+///
+/// `=`右侧为实现了[`Endpoint`](crate::endpoint::Endpoint)的接口描述，函数的返回类型需与其响应类型一致。
+///
+/// ```text
+/// api! {
+///     /// 文档
+///     fn name(token: &str, req: &Req) -> Res = req;
+/// }
+/// ```
+macro_rules! api {
+    ($(
+        $(#[$meta:meta])*
+        fn $name:ident($token:ident: &str $(, $arg:ident: $ty:ty)* $(,)?) -> $res:ty = $endpoint:expr;
+    )*) => {
+        $(
+            $(#[$meta])*
+            #[cfg(feature = "reqwest")]
+            pub async fn $name($token: &str $(, $arg: $ty)*) -> Result<$res, reqwest::Error> {
+                $crate::request::execute(&$endpoint, $token).await
+            }
+        )*
+
+        /// 同步接口，不能在异步运行时中调用
+        #[cfg(feature = "blocking")]
+        pub mod blocking {
+            #[allow(unused_imports)]
+            use super::*;
+            $(
+                $(#[$meta])*
+                pub fn $name($token: &str $(, $arg: $ty)*) -> Result<$res, reqwest::Error> {
+                    $crate::request::blocking::execute(&$endpoint, $token)
+                }
+            )*
+        }
+    };
+}
+
+/// 为请求类型实现JSON格式的POST接口
+///
+/// 可在响应类型后以`idempotent = |req| ...`、`open_kfid = |req| ...`的形式覆盖对应的默认实现。
+macro_rules! json_endpoint {
+    ($req:ty, $path:expr, $res:ty $(, $method:ident = |$this:ident| $body:expr)* $(,)?) => {
+        impl $crate::endpoint::Endpoint for $req {
+            type Response = $res;
+
+            fn path(&self) -> String {
+                $path.to_string()
+            }
+
+            fn body(&self) -> Option<Vec<u8>> {
+                Some(serde_json::to_vec(self).expect("request body should serialize to JSON"))
+            }
+
+            $(json_endpoint!(@method $method, $this, $body);)*
+        }
+    };
+    (@method idempotent, $this:ident, $body:expr) => {
+        fn idempotent(&self) -> bool {
+            let $this = self;
+            $body
+        }
+    };
+    (@method open_kfid, $this:ident, $body:expr) => {
+        fn open_kfid(&self) -> Option<&str> {
+            let $this = self;
+            $body
+        }
+    };
+}
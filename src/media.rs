@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encrypt::random_bytes;
+use crate::endpoint::{with_query, Endpoint};
+use crate::transport::{HttpRequest, Method};
+
+/// 临时素材的类型
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaType {
+    /// 图片，10MB以内，支持JPG、PNG格式
+    Image,
+    /// 语音，2MB以内，播放长度不超过60s，仅支持AMR格式
+    Voice,
+    /// 视频，10MB以内，支持MP4格式
+    Video,
+    /// 普通文件，20MB以内
+    File,
+}
+
+impl MediaType {
+    /// 素材类型在接口中的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            MediaType::Image => "image",
+            MediaType::Voice => "voice",
+            MediaType::Video => "video",
+            MediaType::File => "file",
+        }
+    }
+}
+
+/// 上传临时素材的请求，素材上传后3天内有效
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub media_type: MediaType,
+    pub filename: String,
+    pub data: Vec<u8>,
+    boundary: String,
+}
+
+impl Upload {
+    /// 创建上传请求，`filename`会出现在multipart的`Content-Disposition`中
+    pub fn new(media_type: MediaType, filename: &str, data: Vec<u8>) -> Self {
+        Self {
+            media_type,
+            filename: filename.to_string(),
+            data,
+            boundary: format!("kf-wx-{}", hex::encode(random_bytes())),
+        }
+    }
+}
+
+/// 上传临时素材的响应，`media_id`用于发送图片、语音、视频和文件消息
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    #[serde(rename = "type", default)]
+    pub media_type: String,
+    #[serde(default)]
+    pub media_id: String,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+impl Endpoint for Upload {
+    type Response = UploadRes;
+
+    fn path(&self) -> String {
+        with_query("/media/upload", &[("type", self.media_type.name())])
+    }
+
+    /// multipart/form-data格式的请求体
+    fn body(&self) -> Option<Vec<u8>> {
+        let head = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"media\"; filename=\"{}\"; filelength={}\r\nContent-Type: application/octet-stream\r\n\r\n",
+            self.boundary,
+            self.filename.replace('"', ""),
+            self.data.len()
+        );
+        let tail = format!("\r\n--{}--\r\n", self.boundary);
+        Some([head.as_bytes(), &self.data, tail.as_bytes()].concat())
+    }
+
+    fn to_request(&self, base_url: &str, token: &str) -> HttpRequest {
+        let content_type = format!("multipart/form-data; boundary={}", self.boundary);
+        HttpRequest {
+            method: Method::Post,
+            url: with_query(
+                &format!("{base_url}{}", self.path()),
+                &[("access_token", token)],
+            ),
+            headers: vec![("content-type".to_string(), content_type)],
+            body: self.body().unwrap_or_default(),
+        }
+    }
+}
+
+/// 获取临时素材的请求
+#[derive(Debug, Clone)]
+pub struct GetMedia {
+    pub media_id: String,
+}
+
+impl GetMedia {
+    /// 获取指定的临时素材
+    pub fn new(media_id: &str) -> Self {
+        Self {
+            media_id: media_id.to_string(),
+        }
+    }
+}
+
+/// 临时素材的内容，接口出错时`data`为空
+#[derive(Debug, Clone, Deserialize)]
+pub struct MediaRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl Endpoint for GetMedia {
+    type Response = MediaRes;
+
+    fn method(&self) -> Method {
+        Method::Get
+    }
+
+    fn path(&self) -> String {
+        with_query("/media/get", &[("media_id", &self.media_id)])
+    }
+
+    fn body(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// 成功时响应体为文件内容，出错时为带`errcode`的JSON对象
+    ///
+    /// 文件本身是JSON（如`.json`文件）但不含`errcode`时仍作为文件内容返回。
+    fn parse_response(bytes: &[u8]) -> serde_json::Result<MediaRes> {
+        match serde_json::from_slice::<serde_json::Value>(bytes) {
+            Ok(value) if value.get("errcode").is_some() => serde_json::from_value(value),
+            _ => Ok(MediaRes {
+                errcode: 0,
+                errmsg: "ok".to_string(),
+                data: bytes.to_vec(),
+            }),
+        }
+    }
+}
+
+// 获取临时素材的响应不是JSON，只能通过`Client::call`调用
+api! {
+    /// 上传临时素材
+    fn upload(token: &str, req: &Upload) -> UploadRes = req;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::mock_seq;
+    use crate::Client;
+
+    #[test]
+    fn test_upload_request() {
+        let upload = Upload::new(MediaType::Image, "a.png", b"PNG".to_vec());
+        let req = upload.to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(
+            req.url,
+            "https://qyapi.weixin.qq.com/cgi-bin/media/upload?type=image&access_token=TOKEN"
+        );
+        let boundary = req.headers[0].1.split("boundary=").nth(1).unwrap();
+        let body = String::from_utf8(req.body).unwrap();
+        assert!(body.starts_with(&format!("--{boundary}\r\n")));
+        assert!(body.contains("filename=\"a.png\"; filelength=3\r\n"));
+        assert!(body.ends_with(&format!("\r\n\r\nPNG\r\n--{boundary}--\r\n")));
+    }
+
+    #[test]
+    fn test_get_media_request() {
+        let req =
+            GetMedia::new("MEDIA_ID").to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(req.method, Method::Get);
+        assert_eq!(
+            req.url,
+            "https://qyapi.weixin.qq.com/cgi-bin/media/get?media_id=MEDIA_ID&access_token=TOKEN"
+        );
+        assert!(req.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_client_call() {
+        let transport = mock_seq(&[
+            r#"{"errcode": 0, "errmsg": "", "type": "image", "media_id": "MEDIA_ID", "created_at": "1380000000"}"#,
+            "FILE",
+        ]);
+        let client = Client::with_transport("TOKEN", transport.clone());
+        let res = client
+            .call(&Upload::new(MediaType::Image, "a.png", b"PNG".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(
+            (res.media_type.as_str(), res.media_id.as_str()),
+            ("image", "MEDIA_ID")
+        );
+
+        let res = client.call(&GetMedia::new(&res.media_id)).await.unwrap();
+        assert_eq!(res.data, b"FILE");
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[0].headers[0]
+            .1
+            .starts_with("multipart/form-data; boundary="));
+        assert!(requests[1].url.contains("/media/get?media_id=MEDIA_ID&"));
+    }
+
+    #[test]
+    fn test_media_res() {
+        let res = GetMedia::parse_response(b"\x89PNG").unwrap();
+        assert_eq!(
+            (res.errcode, res.data.as_slice()),
+            (0, b"\x89PNG".as_slice())
+        );
+
+        let res = GetMedia::parse_response(br#"{"errcode": 40007, "errmsg": "invalid media_id"}"#)
+            .unwrap();
+        assert_eq!(res.errcode, 40007);
+        assert!(res.data.is_empty());
+
+        let file = br#"{"errmsg": "not an error"}"#;
+        let res = GetMedia::parse_response(file).unwrap();
+        assert_eq!((res.errcode, res.data.as_slice()), (0, file.as_slice()));
+        assert!(GetMedia::parse_response(br#"{"errcode": "40007"}"#).is_err());
+    }
+}
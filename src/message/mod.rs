@@ -1,8 +1,12 @@
+/// 消息ID
+pub mod msgid;
 /// 撤回消息
 pub mod recall;
 /// 接收消息
 pub mod receive;
 /// 发送消息
 pub mod send;
+/// 会话状态
+pub mod service_state;
 /// 客服欢迎语
 pub mod welcome;
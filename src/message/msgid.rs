@@ -0,0 +1,118 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::send::{is_valid_msgid, BuildErr, MAX_MSGID_LEN};
+
+/// 前缀的最大长度，剩余的24个字符用于保证唯一性
+pub const MAX_PREFIX_LEN: usize = MAX_MSGID_LEN - 24;
+
+/// 同一毫秒内最多生成的msgid个数
+const MAX_PER_MILLI: u32 = 0x1_0000;
+
+/// msgid生成器，生成的msgid由前缀、毫秒时间戳、进程内随机种子和毫秒内序号组成
+///
+/// 同一毫秒内的序号用完时等到下一毫秒再生成，不会回绕产生重复的msgid。
+#[derive(Debug)]
+pub struct MsgidGenerator {
+    prefix: String,
+    seed: u32,
+    /// 上次生成时使用的毫秒时间戳和该毫秒内已生成的个数
+    last: Mutex<(u64, u32)>,
+}
+
+impl MsgidGenerator {
+    /// 使用指定前缀创建生成器，前缀不能超过8个字符且只能包含数字、字母和`_-`
+    pub fn new(prefix: &str) -> Result<Self, BuildErr> {
+        if prefix.len() > MAX_PREFIX_LEN || !(prefix.is_empty() || is_valid_msgid(prefix)) {
+            return Err(BuildErr::InvalidMsgid(prefix.to_string()));
+        }
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u128(now().as_nanos());
+        Ok(Self {
+            prefix: prefix.to_string(),
+            seed: hasher.finish() as u32,
+            last: Mutex::new((0, 0)),
+        })
+    }
+
+    /// 生成新的msgid
+    pub fn generate(&self) -> String {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut millis, mut count) = *last;
+        loop {
+            let current = now_millis();
+            if current > millis {
+                (millis, count) = (current, 0);
+                break;
+            }
+            // 时钟回拨时沿用上次的时间戳，序号用完后等待时钟前进
+            if count < MAX_PER_MILLI {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        *last = (millis, count + 1);
+        format!(
+            "{}{:012x}{:08x}{:04x}",
+            self.prefix,
+            millis & 0xFFFF_FFFF_FFFF,
+            self.seed,
+            count
+        )
+    }
+}
+
+impl Default for MsgidGenerator {
+    fn default() -> Self {
+        Self::new("").unwrap()
+    }
+}
+
+fn now_millis() -> u64 {
+    now().as_millis() as u64
+}
+
+fn now() -> std::time::Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_generate() {
+        let generator = MsgidGenerator::new("kf_wx-01").unwrap();
+        let ids: HashSet<String> = (0..1000).map(|_| generator.generate()).collect();
+        assert_eq!(ids.len(), 1000);
+        for id in ids {
+            assert!(id.starts_with("kf_wx-01"));
+            assert_eq!(id.len(), MAX_MSGID_LEN);
+            assert!(is_valid_msgid(&id));
+        }
+    }
+
+    #[test]
+    fn test_exhausted_milli() {
+        let generator = MsgidGenerator::default();
+        let millis = now_millis();
+        *generator.last.lock().unwrap() = (millis, MAX_PER_MILLI);
+        let id = generator.generate();
+        let generated = u64::from_str_radix(&id[..12], 16).unwrap();
+        assert!(generated > millis);
+        assert!(id.ends_with("0000"));
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        assert!(MsgidGenerator::new("too_long_prefix").is_err());
+        assert!(MsgidGenerator::new("a b").is_err());
+        assert!(MsgidGenerator::new("").is_ok());
+    }
+}
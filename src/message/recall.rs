@@ -1,27 +1,188 @@
-use reqwest::Client;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde::Serialize;
 
-use crate::{account::SimpleRes, constant::BASE_URL};
-/// 请求地址
-fn format_url(token: &str) -> String {
-    format!("{BASE_URL}/recall_msg?access_token={token}")
-}
-
-#[derive(Debug, Serialize)]
-pub struct Message {
-    msgid: String,
-    open_kfid: String,
-}
-
-/// 撤回消息
-pub async fn recall_msg(token: &str, message: &Message) -> Result<SimpleRes, reqwest::Error> {
-    let url = format_url(token);
-    let client = Client::new();
-    client
-        .post(url)
-        .json(message)
-        .send()
-        .await?
-        .json::<SimpleRes>()
-        .await
+use crate::account::SimpleRes;
+use crate::msg_res::{KfEvent, Message, MsgItem, MsgOrigin};
+use crate::send::MessageRes;
+
+/// 可撤回消息的时间窗口（秒）
+pub const RECALL_WINDOW_SECS: u64 = 120;
+
+/// 撤回消息错误类型
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RecallErr {
+    /// 消息不是由客服发送的，只能撤回客服发送的消息
+    Origin(MsgOrigin),
+    /// 消息缺少open_kfid
+    MissingOpenKfid,
+    /// 消息已发送超过2分钟，值为已发送的秒数
+    Expired(u64),
+}
+
+impl std::fmt::Display for RecallErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecallErr::Origin(origin) => write!(
+                f,
+                "only messages sent by a servicer can be recalled, got origin {origin:?}"
+            ),
+            RecallErr::MissingOpenKfid => write!(f, "message has no open_kfid"),
+            RecallErr::Expired(elapsed) => write!(
+                f,
+                "message was sent {elapsed}s ago, recall is allowed within {RECALL_WINDOW_SECS}s"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecallErr {}
+
+/// 撤回消息请求
+#[derive(Debug, Clone, Serialize)]
+pub struct RecallRequest {
+    pub msgid: String,
+    pub open_kfid: String,
+    /// 消息发送的时间戳，用于在本地校验撤回时间窗口
+    #[serde(skip)]
+    pub send_time: Option<u64>,
+}
+
+impl RecallRequest {
+    pub fn new(open_kfid: &str, msgid: &str) -> Self {
+        Self {
+            msgid: msgid.to_string(),
+            open_kfid: open_kfid.to_string(),
+            send_time: None,
+        }
+    }
+
+    /// 撤回刚刚发送的消息，以当前时间作为发送时间
+    pub fn from_sent(open_kfid: &str, res: &MessageRes) -> Self {
+        Self {
+            send_time: Some(now()),
+            ..Self::new(open_kfid, &res.msgid)
+        }
+    }
+
+    /// 撤回通过`sync_msg`拉取到的客服消息
+    pub fn from_item(item: &MsgItem) -> Result<Self, RecallErr> {
+        if item.origin != MsgOrigin::Kf {
+            return Err(RecallErr::Origin(item.origin));
+        }
+        let open_kfid = item.open_kfid.as_ref().ok_or(RecallErr::MissingOpenKfid)?;
+        Ok(Self {
+            send_time: Some(item.send_time),
+            ..Self::new(open_kfid, &item.msgid)
+        })
+    }
+
+    /// 校验消息是否仍在可撤回的时间窗口内，未知发送时间时不做校验
+    pub fn check_window(&self) -> Result<(), RecallErr> {
+        match self.send_time {
+            Some(send_time) => {
+                let elapsed = now().saturating_sub(send_time);
+                if elapsed > RECALL_WINDOW_SECS {
+                    Err(RecallErr::Expired(elapsed))
+                } else {
+                    Ok(())
+                }
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 从`servicer_recall_msg`事件中获取被撤回的消息ID
+pub fn recalled_msgid(item: &MsgItem) -> Option<&str> {
+    match &item.message {
+        Message::Event {
+            event_type,
+            recall_msgid,
+            ..
+        } if *event_type == KfEvent::ServicerRecallMsg => recall_msgid.as_deref(),
+        _ => None,
+    }
+}
+
+json_endpoint!(
+    RecallRequest,
+    "/kf/recall_msg",
+    SimpleRes,
+    open_kfid = |req| Some(req.open_kfid.as_str())
+);
+
+api! {
+    /// 撤回消息
+    fn recall_msg(token: &str, message: &RecallRequest) -> SimpleRes = message;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_str, json, to_value};
+
+    fn gen_item(origin: u8, send_time: u64) -> MsgItem {
+        let str = format!(
+            r#"{{
+                "msgid": "MSG_ID",
+                "open_kfid": "OPEN_KFID",
+                "external_userid": "EXTERNAL_USERID",
+                "send_time": {send_time},
+                "origin": {origin},
+                "msgtype": "text",
+                "text": {{ "content": "hello" }}
+            }}"#
+        );
+        from_str(&str).unwrap()
+    }
+
+    #[test]
+    fn test_from_item() {
+        let item = gen_item(5, now());
+        let req = RecallRequest::from_item(&item).unwrap();
+        assert_eq!(req.check_window(), Ok(()));
+        let expected = json!({"msgid": "MSG_ID", "open_kfid": "OPEN_KFID"});
+        assert_eq!(to_value(&req).unwrap(), expected);
+
+        let item = gen_item(3, now());
+        let result = RecallRequest::from_item(&item);
+        assert_eq!(
+            result.unwrap_err(),
+            RecallErr::Origin(MsgOrigin::WeiXinCustomer)
+        );
+    }
+
+    #[test]
+    fn test_expired() {
+        let item = gen_item(5, now() - 180);
+        let req = RecallRequest::from_item(&item).unwrap();
+        assert!(matches!(req.check_window(), Err(RecallErr::Expired(180..))));
+    }
+
+    #[test]
+    fn test_recalled_msgid() {
+        let str = r#"{
+            "msgid": "MSG_ID",
+            "send_time": 1615478585,
+            "origin": 4,
+            "msgtype": "event",
+            "event": {
+                "event_type": "servicer_recall_msg",
+                "open_kfid": "OPEN_KFID",
+                "external_userid": "EXTERNAL_USERID",
+                "recall_msgid": "RECALL_MSGID",
+                "servicer_userid": "SERVICER_USERID"
+            }
+        }"#;
+        let item: MsgItem = from_str(str).unwrap();
+        assert_eq!(recalled_msgid(&item), Some("RECALL_MSGID"));
+    }
 }
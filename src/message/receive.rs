@@ -1,21 +1,15 @@
-use crate::constant::BASE_URL;
 use crate::msg_res::MsgItem;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::fmt::Debug;
 
-fn format_url(token: &str) -> String {
-    format!("{BASE_URL}/sync_msg?access_token={token}")
-}
-
 #[derive(Deserialize_repr, Serialize_repr, Clone, Debug, Copy)]
 #[repr(u8)]
 pub enum VoiceFormat {
     Amr = 0,
     Silk = 1,
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct SyncMsg {
     pub cursor: Option<String>,
     pub token: Option<String>,
@@ -24,7 +18,20 @@ pub struct SyncMsg {
     pub open_kfid: Option<String>,
 }
 
-#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy)]
+/// 回调中的token可用于拉取消息，`Debug`输出时隐去
+impl Debug for SyncMsg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncMsg")
+            .field("cursor", &self.cursor)
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("limit", &self.limit)
+            .field("voice_format", &self.voice_format)
+            .field("open_kfid", &self.open_kfid)
+            .finish()
+    }
+}
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(u8)]
 pub enum MoreMsg {
     Yes = 1,
@@ -34,20 +41,26 @@ pub enum MoreMsg {
 pub struct MsgRes {
     pub errcode: i32,
     pub errmsg: String,
+    #[serde(default)]
     pub next_cursor: String,
+    #[serde(default = "no_more")]
     pub has_more: MoreMsg,
+    #[serde(default)]
     pub msg_list: Vec<MsgItem>,
 }
 
-/// 接收消息
-pub async fn sync_msg(token: &str, msg: &SyncMsg) -> reqwest::Result<MsgRes> {
-    let url = format_url(token);
-    let client = Client::new();
-    client
-        .post(url)
-        .json(msg)
-        .send()
-        .await?
-        .json::<MsgRes>()
-        .await
+fn no_more() -> MoreMsg {
+    MoreMsg::No
+}
+
+json_endpoint!(
+    SyncMsg,
+    "/kf/sync_msg",
+    MsgRes,
+    open_kfid = |msg| msg.open_kfid.as_deref()
+);
+
+api! {
+    /// 接收消息
+    fn sync_msg(token: &str, msg: &SyncMsg) -> MsgRes = msg;
 }
@@ -1,20 +1,22 @@
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::constant::BASE_URL;
-/// 请求地址
-fn format_url(token: &str) -> String {
-    format!("{BASE_URL}/send_msg?access_token={token}")
-}
+/// 文本消息内容的最大字节数
+pub const MAX_CONTENT_LEN: usize = 2048;
+/// 菜单消息的最大菜单项数
+pub const MAX_MENU_ITEMS: usize = 10;
+/// msgid的最大长度
+pub const MAX_MSGID_LEN: usize = 32;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct MessageRes {
     pub errcode: i32,
     pub errmsg: String,
+    #[serde(default)]
     pub msgid: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone)]
 pub struct Message {
     pub touser: String,
     pub open_kfid: String,
@@ -22,31 +24,49 @@ pub struct Message {
     pub msgtype: MsgType,
 }
 
-#[derive(Debug, Serialize)]
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("touser", &self.touser)?;
+        map.serialize_entry("open_kfid", &self.open_kfid)?;
+        if let Some(msgid) = &self.msgid {
+            map.serialize_entry("msgid", msgid)?;
+        }
+        map.serialize_entry("msgtype", self.msgtype.name())?;
+        map.serialize_entry(self.msgtype.name(), &self.msgtype)?;
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Link {
     pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub desc: Option<String>,
     pub url: String,
     pub thumb_media_id: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MiniProgram {
     pub appid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     pub thumb_media_id: String,
     pub pagepath: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub address: Option<String>,
     pub latitude: f64,
     pub longitude: f64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone)]
 pub enum MsgType {
     Text(String),
     Image(String),
@@ -59,30 +79,513 @@ pub enum MsgType {
     Location(Location),
 }
 
-#[derive(Debug, Serialize)]
+impl MsgType {
+    /// 消息类型在接口中的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            MsgType::Text(_) => "text",
+            MsgType::Image(_) => "image",
+            MsgType::Voice(_) => "voice",
+            MsgType::Video(_) => "video",
+            MsgType::File(_) => "file",
+            MsgType::Link(_) => "link",
+            MsgType::MiniProgram(_) => "miniprogram",
+            MsgType::Menu(_) => "msgmenu",
+            MsgType::Location(_) => "location",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Content<'a> {
+    pub(crate) content: &'a str,
+}
+
+#[derive(Serialize)]
+struct Media<'a> {
+    media_id: &'a str,
+}
+
+/// 序列化为消息类型对应的消息体
+impl Serialize for MsgType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MsgType::Text(content) => Content { content }.serialize(serializer),
+            MsgType::Image(media_id)
+            | MsgType::Voice(media_id)
+            | MsgType::Video(media_id)
+            | MsgType::File(media_id) => Media { media_id }.serialize(serializer),
+            MsgType::Link(link) => link.serialize(serializer),
+            MsgType::MiniProgram(mini_program) => mini_program.serialize(serializer),
+            MsgType::Menu(menu) => menu.serialize(serializer),
+            MsgType::Location(location) => location.serialize(serializer),
+        }
+    }
+}
+
+/// 菜单消息，也用于解析客服发送的菜单消息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Menu {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub head_content: Option<String>,
+    #[serde(default)]
     pub list: Vec<MenuItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tail_content: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone)]
 pub enum MenuItem {
+    /// 回复菜单：(id, content)
     Click(String, String),
+    /// 超链接菜单：(url, content)
     View(String, String),
+    /// 小程序菜单：(appid, pagepath, content)
     MiniProgram(String, String, String),
+    /// 文本：(content, no_newline)
     Text(String, i32),
 }
 
-/// 发送消息
-pub async fn send(token: &str, message: &Message) -> Result<MessageRes, reqwest::Error> {
-    let url = format_url(token);
-    let client = Client::new();
-    client
-        .post(url)
-        .json(message)
-        .send()
-        .await?
-        .json::<MessageRes>()
-        .await
+impl MenuItem {
+    /// 菜单项类型在接口中的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            MenuItem::Click(..) => "click",
+            MenuItem::View(..) => "view",
+            MenuItem::MiniProgram(..) => "miniprogram",
+            MenuItem::Text(..) => "text",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum MenuItemBody<'a> {
+    Click {
+        id: &'a str,
+        content: &'a str,
+    },
+    View {
+        url: &'a str,
+        content: &'a str,
+    },
+    MiniProgram {
+        appid: &'a str,
+        pagepath: &'a str,
+        content: &'a str,
+    },
+    Text {
+        content: &'a str,
+        no_newline: i32,
+    },
+}
+
+impl Serialize for MenuItem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let body = match self {
+            MenuItem::Click(id, content) => MenuItemBody::Click { id, content },
+            MenuItem::View(url, content) => MenuItemBody::View { url, content },
+            MenuItem::MiniProgram(appid, pagepath, content) => MenuItemBody::MiniProgram {
+                appid,
+                pagepath,
+                content,
+            },
+            MenuItem::Text(content, no_newline) => MenuItemBody::Text {
+                content,
+                no_newline: *no_newline,
+            },
+        };
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("type", self.name())?;
+        map.serialize_entry(self.name(), &body)?;
+        map.end()
+    }
+}
+
+/// 解析菜单项时各类型内容字段的并集
+#[derive(Deserialize)]
+struct MenuItemFields {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    appid: String,
+    #[serde(default)]
+    pagepath: String,
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    no_newline: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TaggedMenuItem {
+    Click { click: MenuItemFields },
+    View { view: MenuItemFields },
+    Miniprogram { miniprogram: MenuItemFields },
+    Text { text: MenuItemFields },
+}
+
+impl<'de> Deserialize<'de> for MenuItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let item = match TaggedMenuItem::deserialize(deserializer)? {
+            TaggedMenuItem::Click { click } => MenuItem::Click(click.id, click.content),
+            TaggedMenuItem::View { view } => MenuItem::View(view.url, view.content),
+            TaggedMenuItem::Miniprogram { miniprogram: m } => {
+                MenuItem::MiniProgram(m.appid, m.pagepath, m.content)
+            }
+            TaggedMenuItem::Text { text } => MenuItem::Text(text.content, text.no_newline),
+        };
+        Ok(item)
+    }
+}
+
+/// 构建消息错误类型
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BuildErr {
+    /// 未指定接收消息的客户
+    MissingTouser,
+    /// 未指定发送消息的客服账号
+    MissingOpenKfid,
+    /// 文本内容超出长度限制，值为实际字节数
+    ContentTooLong(usize),
+    /// 菜单项超出数量限制，值为实际数量
+    TooManyMenuItems(usize),
+    /// msgid格式不合法
+    InvalidMsgid(String),
+}
+
+impl std::fmt::Display for BuildErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildErr::MissingTouser => write!(f, "touser is required"),
+            BuildErr::MissingOpenKfid => write!(f, "open_kfid is required"),
+            BuildErr::ContentTooLong(len) => write!(
+                f,
+                "text content is {len} bytes, at most {MAX_CONTENT_LEN} allowed"
+            ),
+            BuildErr::TooManyMenuItems(count) => write!(
+                f,
+                "menu has {count} items, at most {MAX_MENU_ITEMS} allowed"
+            ),
+            BuildErr::InvalidMsgid(msgid) => write!(
+                f,
+                "invalid msgid {msgid:?}, expected at most {MAX_MSGID_LEN} characters of [0-9a-zA-Z_-]"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildErr {}
+
+/// 判断msgid是否合法：不多于32个字符，且只包含数字、字母和`_-`
+pub fn is_valid_msgid(msgid: &str) -> bool {
+    !msgid.is_empty()
+        && msgid.len() <= MAX_MSGID_LEN
+        && msgid
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// 待发送消息的构建器
+#[derive(Debug)]
+pub struct OutgoingMessage {
+    touser: Option<String>,
+    open_kfid: Option<String>,
+    msgid: Option<String>,
+    msgtype: MsgType,
+}
+
+impl OutgoingMessage {
+    fn new(msgtype: MsgType) -> Self {
+        Self {
+            touser: None,
+            open_kfid: None,
+            msgid: None,
+            msgtype,
+        }
+    }
+    /// 文本消息
+    pub fn text(content: impl Into<String>) -> Self {
+        Self::new(MsgType::Text(content.into()))
+    }
+    /// 图片消息
+    pub fn image(media_id: impl Into<String>) -> Self {
+        Self::new(MsgType::Image(media_id.into()))
+    }
+    /// 语音消息
+    pub fn voice(media_id: impl Into<String>) -> Self {
+        Self::new(MsgType::Voice(media_id.into()))
+    }
+    /// 视频消息
+    pub fn video(media_id: impl Into<String>) -> Self {
+        Self::new(MsgType::Video(media_id.into()))
+    }
+    /// 文件消息
+    pub fn file(media_id: impl Into<String>) -> Self {
+        Self::new(MsgType::File(media_id.into()))
+    }
+    /// 图文链接消息
+    pub fn link(link: Link) -> Self {
+        Self::new(MsgType::Link(link))
+    }
+    /// 小程序消息
+    pub fn mini_program(mini_program: MiniProgram) -> Self {
+        Self::new(MsgType::MiniProgram(mini_program))
+    }
+    /// 地理位置消息
+    pub fn location(location: Location) -> Self {
+        Self::new(MsgType::Location(location))
+    }
+    /// 菜单消息
+    pub fn menu() -> MenuBuilder {
+        MenuBuilder::default()
+    }
+    /// 接收消息的客户
+    pub fn to(mut self, touser: impl Into<String>) -> Self {
+        self.touser = Some(touser.into());
+        self
+    }
+    /// 发送消息的客服账号
+    pub fn from_kf(mut self, open_kfid: impl Into<String>) -> Self {
+        self.open_kfid = Some(open_kfid.into());
+        self
+    }
+    /// 指定消息ID
+    pub fn msgid(mut self, msgid: impl Into<String>) -> Self {
+        self.msgid = Some(msgid.into());
+        self
+    }
+    /// 校验并生成消息
+    pub fn build(self) -> Result<Message, BuildErr> {
+        let touser = self.touser.ok_or(BuildErr::MissingTouser)?;
+        let open_kfid = self.open_kfid.ok_or(BuildErr::MissingOpenKfid)?;
+        if let Some(msgid) = &self.msgid {
+            if !is_valid_msgid(msgid) {
+                return Err(BuildErr::InvalidMsgid(msgid.clone()));
+            }
+        }
+        match &self.msgtype {
+            MsgType::Text(content) if content.len() > MAX_CONTENT_LEN => {
+                return Err(BuildErr::ContentTooLong(content.len()));
+            }
+            MsgType::Menu(menu) if menu.list.len() > MAX_MENU_ITEMS => {
+                return Err(BuildErr::TooManyMenuItems(menu.list.len()));
+            }
+            _ => {}
+        }
+        Ok(Message {
+            touser,
+            open_kfid,
+            msgid: self.msgid,
+            msgtype: self.msgtype,
+        })
+    }
+}
+
+/// 菜单消息的构建器
+#[derive(Debug, Default)]
+pub struct MenuBuilder {
+    menu: Menu,
+}
+
+impl MenuBuilder {
+    /// 起始文本
+    pub fn head(mut self, content: impl Into<String>) -> Self {
+        self.menu.head_content = Some(content.into());
+        self
+    }
+    /// 结束文本
+    pub fn tail(mut self, content: impl Into<String>) -> Self {
+        self.menu.tail_content = Some(content.into());
+        self
+    }
+    /// 回复菜单
+    pub fn click(mut self, id: impl Into<String>, content: impl Into<String>) -> Self {
+        let item = MenuItem::Click(id.into(), content.into());
+        self.menu.list.push(item);
+        self
+    }
+    /// 超链接菜单
+    pub fn view(mut self, url: impl Into<String>, content: impl Into<String>) -> Self {
+        let item = MenuItem::View(url.into(), content.into());
+        self.menu.list.push(item);
+        self
+    }
+    /// 小程序菜单
+    pub fn mini_program(
+        mut self,
+        appid: impl Into<String>,
+        pagepath: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Self {
+        let item = MenuItem::MiniProgram(appid.into(), pagepath.into(), content.into());
+        self.menu.list.push(item);
+        self
+    }
+    /// 文本，`no_newline`为`true`时不换行
+    pub fn text(mut self, content: impl Into<String>, no_newline: bool) -> Self {
+        let item = MenuItem::Text(content.into(), no_newline as i32);
+        self.menu.list.push(item);
+        self
+    }
+    /// 接收消息的客户
+    pub fn to(self, touser: impl Into<String>) -> OutgoingMessage {
+        OutgoingMessage::from(self).to(touser)
+    }
+    /// 发送消息的客服账号
+    pub fn from_kf(self, open_kfid: impl Into<String>) -> OutgoingMessage {
+        OutgoingMessage::from(self).from_kf(open_kfid)
+    }
+    /// 生成菜单
+    pub fn into_menu(self) -> Menu {
+        self.menu
+    }
+}
+
+impl From<MsgType> for OutgoingMessage {
+    fn from(value: MsgType) -> Self {
+        OutgoingMessage::new(value)
+    }
+}
+
+impl From<MenuBuilder> for OutgoingMessage {
+    fn from(value: MenuBuilder) -> Self {
+        OutgoingMessage::new(MsgType::Menu(value.menu))
+    }
+}
+
+json_endpoint!(
+    Message,
+    "/kf/send_msg",
+    MessageRes,
+    idempotent = |message| message.msgid.is_some(),
+    open_kfid = |message| Some(message.open_kfid.as_str())
+);
+
+api! {
+    /// 发送消息
+    fn send(token: &str, message: &Message) -> MessageRes = message;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, to_value};
+
+    #[test]
+    fn test_text_message() {
+        let message = OutgoingMessage::text("hello world")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .msgid("MSGID")
+            .build()
+            .unwrap();
+        let expected = json!({
+            "touser": "EXTERNAL_USERID",
+            "open_kfid": "OPEN_KFID",
+            "msgid": "MSGID",
+            "msgtype": "text",
+            "text": {
+                "content": "hello world"
+            }
+        });
+        assert_eq!(to_value(&message).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_image_message() {
+        let message = OutgoingMessage::image("MEDIA_ID")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let expected = json!({
+            "touser": "EXTERNAL_USERID",
+            "open_kfid": "OPEN_KFID",
+            "msgtype": "image",
+            "image": {
+                "media_id": "MEDIA_ID"
+            }
+        });
+        assert_eq!(to_value(&message).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_menu_message() {
+        let message = OutgoingMessage::menu()
+            .head("您对本次服务是否满意呢? ")
+            .click("101", "满意")
+            .view("https://work.weixin.qq.com", "点击跳转到自助查询页面")
+            .mini_program("wx123123123123123", "pages/index", "打开小程序")
+            .text("这是可插入的文本", true)
+            .tail("欢迎再次光临")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build()
+            .unwrap();
+        let expected = json!({
+            "touser": "EXTERNAL_USERID",
+            "open_kfid": "OPEN_KFID",
+            "msgtype": "msgmenu",
+            "msgmenu": {
+                "head_content": "您对本次服务是否满意呢? ",
+                "list": [
+                    {"type": "click", "click": {"id": "101", "content": "满意"}},
+                    {"type": "view", "view": {"url": "https://work.weixin.qq.com", "content": "点击跳转到自助查询页面"}},
+                    {"type": "miniprogram", "miniprogram": {"appid": "wx123123123123123", "pagepath": "pages/index", "content": "打开小程序"}},
+                    {"type": "text", "text": {"content": "这是可插入的文本", "no_newline": 1}}
+                ],
+                "tail_content": "欢迎再次光临"
+            }
+        });
+        assert_eq!(to_value(&message).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_build_err() {
+        let result = OutgoingMessage::text("hi").from_kf("OPEN_KFID").build();
+        assert_eq!(result.unwrap_err(), BuildErr::MissingTouser);
+
+        let result = OutgoingMessage::text("hi").to("EXTERNAL_USERID").build();
+        assert_eq!(result.unwrap_err(), BuildErr::MissingOpenKfid);
+
+        let content = "a".repeat(MAX_CONTENT_LEN + 1);
+        let result = OutgoingMessage::text(content)
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .build();
+        let err = result.unwrap_err();
+        assert_eq!(err, BuildErr::ContentTooLong(2049));
+        assert_eq!(
+            err.to_string(),
+            "text content is 2049 bytes, at most 2048 allowed"
+        );
+
+        let menu = (0..=MAX_MENU_ITEMS).fold(OutgoingMessage::menu(), |menu, i| {
+            menu.click(i.to_string(), "item")
+        });
+        let result = menu.to("EXTERNAL_USERID").from_kf("OPEN_KFID").build();
+        assert_eq!(result.unwrap_err(), BuildErr::TooManyMenuItems(11));
+
+        let result = OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf("OPEN_KFID")
+            .msgid("msg id")
+            .build();
+        assert_eq!(
+            result.unwrap_err(),
+            BuildErr::InvalidMsgid("msg id".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_valid_msgid() {
+        assert!(is_valid_msgid("abc_DEF-123"));
+        assert!(!is_valid_msgid(""));
+        assert!(!is_valid_msgid(&"a".repeat(MAX_MSGID_LEN + 1)));
+        assert!(!is_valid_msgid("消息"));
+    }
 }
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+/// 会话状态
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[repr(u8)]
+pub enum ServiceState {
+    /// 未处理，新会话接入
+    #[default]
+    Untreated = 0,
+    /// 由智能助手接待
+    Bot = 1,
+    /// 待接入池排队中
+    Pool = 2,
+    /// 由人工接待
+    Servicer = 3,
+    /// 已结束/未开始
+    Ended = 4,
+}
+
+/// 获取会话状态请求
+#[derive(Debug, Clone, Serialize)]
+pub struct GetServiceState {
+    pub open_kfid: String,
+    pub external_userid: String,
+}
+
+impl GetServiceState {
+    pub fn new(open_kfid: &str, external_userid: &str) -> Self {
+        Self {
+            open_kfid: open_kfid.to_string(),
+            external_userid: external_userid.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceStateRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    #[serde(default)]
+    pub service_state: ServiceState,
+    /// 接待人员的userid，仅人工接待时有效
+    #[serde(default)]
+    pub servicer_userid: Option<String>,
+}
+
+/// 变更会话状态请求
+#[derive(Debug, Clone, Serialize)]
+pub struct TransServiceState {
+    pub open_kfid: String,
+    pub external_userid: String,
+    pub service_state: ServiceState,
+    /// 接待人员的userid，变更为人工接待时必填
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servicer_userid: Option<String>,
+}
+
+impl TransServiceState {
+    pub fn new(open_kfid: &str, external_userid: &str, service_state: ServiceState) -> Self {
+        Self {
+            open_kfid: open_kfid.to_string(),
+            external_userid: external_userid.to_string(),
+            service_state,
+            servicer_userid: None,
+        }
+    }
+
+    /// 转给指定的接待人员
+    pub fn servicer(mut self, userid: &str) -> Self {
+        self.service_state = ServiceState::Servicer;
+        self.servicer_userid = Some(userid.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransServiceStateRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    /// 用于发送回复语或结束语的code，只在会话首次变更为对应状态时返回
+    #[serde(default)]
+    pub msg_code: Option<String>,
+}
+
+json_endpoint!(
+    GetServiceState,
+    "/kf/service_state/get",
+    ServiceStateRes,
+    open_kfid = |req| Some(req.open_kfid.as_str())
+);
+
+// 重复请求不会再返回msg_code
+json_endpoint!(
+    TransServiceState,
+    "/kf/service_state/trans",
+    TransServiceStateRes,
+    idempotent = |_req| false,
+    open_kfid = |req| Some(req.open_kfid.as_str())
+);
+
+api! {
+    /// 获取会话状态
+    fn get(token: &str, open_kfid: &str, external_userid: &str) -> ServiceStateRes =
+        GetServiceState::new(open_kfid, external_userid);
+    /// 变更会话状态
+    fn trans(token: &str, req: &TransServiceState) -> TransServiceStateRes = req;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_str, json, to_value};
+
+    #[test]
+    fn test_trans_body() {
+        let req = TransServiceState::new("OPEN_KFID", "EXTERNAL_USERID", ServiceState::Pool);
+        let expected = json!({
+            "open_kfid": "OPEN_KFID",
+            "external_userid": "EXTERNAL_USERID",
+            "service_state": 2
+        });
+        assert_eq!(to_value(&req).unwrap(), expected);
+
+        let req = req.servicer("SERVICER_USERID");
+        assert_eq!(to_value(&req).unwrap()["service_state"], 3);
+        assert_eq!(
+            to_value(&req).unwrap()["servicer_userid"],
+            "SERVICER_USERID"
+        );
+    }
+
+    #[test]
+    fn test_state_res() {
+        let str = r#"{
+            "errcode": 0,
+            "errmsg": "ok",
+            "service_state": 3,
+            "servicer_userid": "zhangsan"
+        }"#;
+        let res: ServiceStateRes = from_str(str).unwrap();
+        assert_eq!(res.service_state, ServiceState::Servicer);
+        assert_eq!(res.servicer_userid.as_deref(), Some("zhangsan"));
+    }
+}
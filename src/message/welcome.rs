@@ -1,46 +1,217 @@
-use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::constant::BASE_URL;
-fn format_url(token: &str) -> String {
-    format!("{BASE_URL}/send_msg_on_evnet?access=token={}", token)
-}
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::msg_res::{Message, MsgItem};
+use crate::send::{Content, Menu};
 
-#[derive(Debug, Deserialize)]
+/// 事件响应消息code的有效期（秒）
+pub const CODE_VALIDITY_SECS: u64 = 20;
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct WelcomeRes {
     pub errcode: i32,
     pub errmsg: String,
+    #[serde(default)]
     pub msgid: String,
 }
 
-#[derive(Debug, Serialize)]
+/// 发送事件响应消息所需的code
+///
+/// 来自`enter_session`事件的`welcome_code`或会话状态变更事件的`msg_code`，
+/// 自事件发生起20秒内有效，且只能使用一次。
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EventCode {
+    pub code: String,
+    /// 事件发生的时间戳
+    pub send_time: u64,
+}
+
+impl EventCode {
+    /// 从事件消息中提取code，消息不是事件或不带code时返回`None`
+    pub fn from_item(item: &MsgItem) -> Option<Self> {
+        match &item.message {
+            Message::Event {
+                welcome_code,
+                msg_code,
+                ..
+            } => welcome_code
+                .as_ref()
+                .or(msg_code.as_ref())
+                .map(|code| Self {
+                    code: code.clone(),
+                    send_time: item.send_time,
+                }),
+            _ => None,
+        }
+    }
+
+    /// 判断code是否已超过有效期
+    pub fn is_expired(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        now > self.send_time + CODE_VALIDITY_SECS
+    }
+
+    /// 生成事件响应消息
+    pub fn welcome(self, msgtype: MsgType) -> Welcome {
+        Welcome::new(&self.code, msgtype)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum MsgType {
     Text(String),
-    Menu(Vec<MenuItem>),
+    Menu(Menu),
 }
 
-#[derive(Debug, Serialize)]
-pub enum MenuItem {
-    Click(String, String),
-    View(String, String),
-    Miniprogram(String, String, String),
-    Text(String, String),
+impl MsgType {
+    /// 消息类型在接口中的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            MsgType::Text(_) => "text",
+            MsgType::Menu(_) => "msgmenu",
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
+/// 事件响应消息
+#[derive(Debug, Clone)]
 pub struct Welcome {
     pub code: String,
-    pub msgid: String,
-    msgtype: MsgType,
-}
-
-pub async fn send_welcome(token: &str, welcome: &Welcome) -> Result<WelcomeRes, reqwest::Error> {
-    let url = format_url(token);
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .json(welcome)
-        .send()
-        .await?
-        .json::<WelcomeRes>()
-        .await
+    pub msgid: Option<String>,
+    pub msgtype: MsgType,
+}
+
+impl Welcome {
+    pub fn new(code: &str, msgtype: MsgType) -> Self {
+        Self {
+            code: code.to_string(),
+            msgid: None,
+            msgtype,
+        }
+    }
+
+    /// 文本欢迎语
+    pub fn text(code: &str, content: &str) -> Self {
+        Self::new(code, MsgType::Text(content.to_string()))
+    }
+
+    /// 菜单欢迎语
+    pub fn menu(code: &str, menu: Menu) -> Self {
+        Self::new(code, MsgType::Menu(menu))
+    }
+
+    /// 指定消息ID
+    pub fn msgid(mut self, msgid: &str) -> Self {
+        self.msgid = Some(msgid.to_string());
+        self
+    }
+}
+
+impl Serialize for Welcome {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", &self.code)?;
+        if let Some(msgid) = &self.msgid {
+            map.serialize_entry("msgid", msgid)?;
+        }
+        map.serialize_entry("msgtype", self.msgtype.name())?;
+        match &self.msgtype {
+            MsgType::Text(content) => map.serialize_entry("text", &Content { content })?,
+            MsgType::Menu(menu) => map.serialize_entry("msgmenu", menu)?,
+        }
+        map.end()
+    }
+}
+
+json_endpoint!(
+    Welcome,
+    "/kf/send_msg_on_event",
+    WelcomeRes,
+    idempotent = |welcome| welcome.msgid.is_some()
+);
+
+api! {
+    /// 发送欢迎语等事件响应消息
+    fn send_welcome(token: &str, welcome: &Welcome) -> WelcomeRes = welcome;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::Endpoint;
+    use crate::send::OutgoingMessage;
+    use serde_json::{from_str, json, to_value};
+
+    #[test]
+    fn test_endpoint() {
+        let req =
+            Welcome::text("CODE", "hi").to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(
+            req.url,
+            "https://qyapi.weixin.qq.com/cgi-bin/kf/send_msg_on_event?access_token=TOKEN"
+        );
+    }
+
+    #[test]
+    fn test_text_welcome() {
+        let welcome = Welcome::text("CODE", "欢迎咨询").msgid("MSG_ID");
+        let expected = json!({
+            "code": "CODE",
+            "msgid": "MSG_ID",
+            "msgtype": "text",
+            "text": {
+                "content": "欢迎咨询"
+            }
+        });
+        assert_eq!(to_value(&welcome).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_menu_welcome() {
+        let menu = OutgoingMessage::menu()
+            .head("请选择")
+            .click("101", "查询订单")
+            .into_menu();
+        let welcome = Welcome::menu("CODE", menu);
+        let expected = json!({
+            "code": "CODE",
+            "msgtype": "msgmenu",
+            "msgmenu": {
+                "head_content": "请选择",
+                "list": [
+                    {"type": "click", "click": {"id": "101", "content": "查询订单"}}
+                ]
+            }
+        });
+        assert_eq!(to_value(&welcome).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_event_code() {
+        let str = r#"{
+            "msgid": "MSG_ID",
+            "send_time": 1615478585,
+            "origin": 4,
+            "msgtype": "event",
+            "event": {
+                "event_type": "enter_session",
+                "open_kfid": "OPEN_KFID",
+                "external_userid": "EXTERNAL_USERID",
+                "scene": "123",
+                "scene_param": "abc",
+                "welcome_code": "WELCOME_CODE"
+            }
+        }"#;
+        let item: MsgItem = from_str(str).unwrap();
+        let code = EventCode::from_item(&item).unwrap();
+        assert_eq!(code.code, "WELCOME_CODE");
+        assert!(code.is_expired());
+        let welcome = code.welcome(MsgType::Text("hi".to_string()));
+        assert_eq!(welcome.code, "WELCOME_CODE");
+    }
 }
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 指标收集器，由客户端、消息拉取和回调处理调用
+///
+/// 所有方法都有空的默认实现，只需实现关心的指标。
+#[allow(unused_variables)]
+pub trait MetricsSink: Send + Sync + 'static {
+    /// 一次接口调用完成（包括重试），`errcode`为`None`表示没有得到可解析的响应
+    fn api_call(&self, endpoint: &str, latency: Duration, errcode: Option<i32>) {}
+
+    /// 成功获取了新的access_token
+    fn token_refresh(&self) {}
+
+    /// 拉取到一条消息，`lag`为拉取时间与消息发送时间的差
+    fn sync_lag(&self, open_kfid: &str, lag: Duration) {}
+
+    /// 回调请求未通过校验，`action`为`verify`或`decrypt`
+    fn callback_rejected(&self, action: &str) {}
+}
+
+impl<T: MetricsSink> MetricsSink for Arc<T> {
+    fn api_call(&self, endpoint: &str, latency: Duration, errcode: Option<i32>) {
+        (**self).api_call(endpoint, latency, errcode)
+    }
+
+    fn token_refresh(&self) {
+        (**self).token_refresh()
+    }
+
+    fn sync_lag(&self, open_kfid: &str, lag: Duration) {
+        (**self).sync_lag(open_kfid, lag)
+    }
+
+    fn callback_rejected(&self, action: &str) {
+        (**self).callback_rejected(action)
+    }
+}
+
+/// 基于`metrics`库的指标收集器，可配合Prometheus等任意exporter使用
+///
+/// | 指标 | 类型 | 标签 |
+/// | --- | --- | --- |
+/// | `kf_wx_api_requests_total` | counter | `endpoint`, `result` (`ok`/`errcode`/`error`) |
+/// | `kf_wx_api_request_duration_seconds` | histogram | `endpoint` |
+/// | `kf_wx_api_errcode_total` | counter | `endpoint`, `errcode` |
+/// | `kf_wx_token_refresh_total` | counter | |
+/// | `kf_wx_sync_lag_seconds` | histogram | `open_kfid` |
+/// | `kf_wx_callback_rejected_total` | counter | `action` |
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsRecorder;
+
+#[cfg(feature = "metrics")]
+impl MetricsSink for MetricsRecorder {
+    fn api_call(&self, endpoint: &str, latency: Duration, errcode: Option<i32>) {
+        let result = match errcode {
+            Some(0) => "ok",
+            Some(_) => "errcode",
+            None => "error",
+        };
+        let endpoint = endpoint.to_string();
+        ::metrics::counter!(
+            "kf_wx_api_requests_total",
+            "endpoint" => endpoint.clone(),
+            "result" => result
+        )
+        .increment(1);
+        ::metrics::histogram!(
+            "kf_wx_api_request_duration_seconds",
+            "endpoint" => endpoint.clone()
+        )
+        .record(latency.as_secs_f64());
+        if let Some(errcode) = errcode.filter(|errcode| *errcode != 0) {
+            ::metrics::counter!(
+                "kf_wx_api_errcode_total",
+                "endpoint" => endpoint,
+                "errcode" => errcode.to_string()
+            )
+            .increment(1);
+        }
+    }
+
+    fn token_refresh(&self) {
+        ::metrics::counter!("kf_wx_token_refresh_total").increment(1);
+    }
+
+    fn sync_lag(&self, open_kfid: &str, lag: Duration) {
+        ::metrics::histogram!("kf_wx_sync_lag_seconds", "open_kfid" => open_kfid.to_string())
+            .record(lag.as_secs_f64());
+    }
+
+    fn callback_rejected(&self, action: &str) {
+        ::metrics::counter!("kf_wx_callback_rejected_total", "action" => action.to_string())
+            .increment(1);
+    }
+}
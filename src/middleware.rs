@@ -0,0 +1,326 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::dispatcher::{BoxFuture, Context, Dispatcher, Flow};
+use crate::msg_res::{MsgItem, MsgOrigin};
+
+/// 消息处理中间件
+///
+/// 中间件可以在调用`next.run(ctx)`前后执行逻辑，也可以不调用`next`直接丢弃消息，
+/// 或者用修改后的消息构造新的[`Context`]交给`next`。
+pub trait Middleware: Send + Sync {
+    fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow>;
+}
+
+/// 中间件链中剩余的中间件和最终的处理器
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    dispatcher: &'a Dispatcher,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(middlewares: &'a [Arc<dyn Middleware>], dispatcher: &'a Dispatcher) -> Self {
+        Self {
+            middlewares,
+            dispatcher,
+        }
+    }
+
+    /// 将消息交给下一个中间件，没有中间件时交给处理器
+    pub fn run(self, ctx: Context) -> BoxFuture<'a, Flow> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => {
+                let next = Next::new(rest, self.dispatcher);
+                middleware.handle(ctx, next)
+            }
+            None => Box::pin(self.dispatcher.route(ctx)),
+        }
+    }
+}
+
+/// 按msgid去重，丢弃最近已处理过或正在处理的消息
+///
+/// 只有后续处理返回[`Flow::Stop`]时才记住msgid；返回[`Flow::Next`]、处理器panic或处理被取消时
+/// 忘记该msgid，消息再次到达时会重新处理。
+#[derive(Debug)]
+pub struct Dedup {
+    capacity: usize,
+    seen: Mutex<(HashSet<String>, VecDeque<String>)>,
+}
+
+impl Dedup {
+    /// 最多记住`capacity`条最近的msgid
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: Mutex::new((HashSet::new(), VecDeque::new())),
+        }
+    }
+
+    /// 记录msgid，已存在时返回`false`
+    fn insert(&self, msgid: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        let (set, queue) = &mut *seen;
+        if !set.insert(msgid.to_string()) {
+            return false;
+        }
+        queue.push_back(msgid.to_string());
+        if queue.len() > self.capacity {
+            if let Some(oldest) = queue.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// 忘记msgid
+    fn remove(&self, msgid: &str) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let (set, queue) = &mut *seen;
+        if set.remove(msgid) {
+            queue.retain(|id| id != msgid);
+        }
+    }
+}
+
+/// 正在处理的msgid，处理完成前被丢弃时（panic或取消）从去重记录中移除
+struct Pending<'a> {
+    dedup: &'a Dedup,
+    msgid: String,
+    handled: bool,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if !self.handled {
+            self.dedup.remove(&self.msgid);
+        }
+    }
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl Middleware for Dedup {
+    fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow> {
+        if !self.insert(&ctx.item.msgid) {
+            return Box::pin(async { Flow::Stop });
+        }
+        let pending = Pending {
+            dedup: self,
+            msgid: ctx.item.msgid.clone(),
+            handled: false,
+        };
+        Box::pin(async move {
+            let mut pending = pending;
+            let flow = next.run(ctx).await;
+            pending.handled = flow == Flow::Stop;
+            flow
+        })
+    }
+}
+
+/// 丢弃指定来源的消息，如`IgnoreOrigin(MsgOrigin::Kf)`可过滤客服自己发送的消息
+#[derive(Debug, Clone, Copy)]
+pub struct IgnoreOrigin(pub MsgOrigin);
+
+impl Middleware for IgnoreOrigin {
+    fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow> {
+        if ctx.item.origin == self.0 {
+            Box::pin(async { Flow::Stop })
+        } else {
+            next.run(ctx)
+        }
+    }
+}
+
+/// 只保留`predicate`返回`true`的消息，可用于黑名单、工作时间等过滤
+pub struct Filter<P> {
+    predicate: P,
+}
+
+impl<P> Filter<P>
+where
+    P: Fn(&MsgItem) -> bool + Send + Sync,
+{
+    pub fn new(predicate: P) -> Self {
+        Self { predicate }
+    }
+}
+
+impl<P> Middleware for Filter<P>
+where
+    P: Fn(&MsgItem) -> bool + Send + Sync,
+{
+    fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow> {
+        if (self.predicate)(&ctx.item) {
+            next.run(ctx)
+        } else {
+            Box::pin(async { Flow::Stop })
+        }
+    }
+}
+
+/// 在交给后续处理前修改消息
+pub struct Map<F> {
+    f: F,
+}
+
+impl<F> Map<F>
+where
+    F: Fn(MsgItem) -> MsgItem + Send + Sync,
+{
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<F> Middleware for Map<F>
+where
+    F: Fn(MsgItem) -> MsgItem + Send + Sync,
+{
+    fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow> {
+        let item = (self.f)(ctx.item.as_ref().clone());
+        next.run(Context::new(ctx.client, item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg_res::Message;
+    use crate::transport::mock;
+    use serde_json::from_str;
+
+    fn text_item(msgid: &str, origin: u8) -> MsgItem {
+        let str = format!(
+            r#"{{
+                "msgid": "{msgid}",
+                "open_kfid": "OPEN_KFID",
+                "external_userid": "EXTERNAL_USERID",
+                "send_time": 1615478585,
+                "origin": {origin},
+                "msgtype": "text",
+                "text": {{ "content": "hello" }}
+            }}"#
+        );
+        from_str(&str).unwrap()
+    }
+
+    /// 记录经过的消息
+    struct Audit(Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for Audit {
+        fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow> {
+            Box::pin(async move {
+                let msgid = ctx.item.msgid.clone();
+                let flow = next.run(ctx).await;
+                self.0.lock().unwrap().push(format!("{msgid}:{flow:?}"));
+                flow
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        let audit = Arc::new(Mutex::new(vec![]));
+        let handled = Arc::new(Mutex::new(vec![]));
+        let handled_clone = handled.clone();
+        let dispatcher = Dispatcher::new()
+            .layer(Audit(audit.clone()))
+            .layer(IgnoreOrigin(MsgOrigin::Kf))
+            .layer(Dedup::new(16))
+            .layer(Map::new(|mut item: MsgItem| {
+                if let Message::Text { content, .. } = &mut item.message {
+                    *content = content.to_uppercase();
+                }
+                item
+            }))
+            .on_text(move |ctx: Context| {
+                let handled = handled_clone.clone();
+                async move {
+                    let content = ctx.item.message.text_content().unwrap().to_string();
+                    handled.lock().unwrap().push(content);
+                    Flow::Stop
+                }
+            });
+        let client = mock::client();
+
+        dispatcher.dispatch(&client, text_item("1", 3)).await;
+        dispatcher.dispatch(&client, text_item("1", 3)).await;
+        dispatcher.dispatch(&client, text_item("2", 5)).await;
+
+        assert_eq!(*handled.lock().unwrap(), ["HELLO"]);
+        assert_eq!(*audit.lock().unwrap(), ["1:Stop", "1:Stop", "2:Stop"]);
+    }
+
+    #[tokio::test]
+    async fn test_filter() {
+        let dispatcher = Dispatcher::new()
+            .layer(Filter::new(|item: &MsgItem| {
+                item.external_userid.as_deref() != Some("EXTERNAL_USERID")
+            }))
+            .fallback(|_| async { Flow::Next });
+        let client = mock::client();
+        let flow = dispatcher.dispatch(&client, text_item("1", 3)).await;
+        assert_eq!(flow, Flow::Stop);
+
+        let mut item = text_item("2", 3);
+        item.external_userid = Some("OTHER_USERID".to_string());
+        let flow = dispatcher.dispatch(&client, item).await;
+        assert_eq!(flow, Flow::Next);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_unhandled() {
+        let calls = Arc::new(Mutex::new(0));
+        let calls_clone = calls.clone();
+        let dispatcher = Arc::new(Dispatcher::new().layer(Dedup::new(16)).on_text(move |_| {
+            let calls = calls_clone.clone();
+            async move {
+                let count = {
+                    let mut calls = calls.lock().unwrap();
+                    *calls += 1;
+                    *calls
+                };
+                match count {
+                    1 => Flow::Next,
+                    2 => panic!("handler failed"),
+                    _ => Flow::Stop,
+                }
+            }
+        }));
+        let client = mock::client();
+
+        assert_eq!(
+            dispatcher.dispatch(&client, text_item("1", 3)).await,
+            Flow::Next
+        );
+        let (panicking, task_client) = (dispatcher.clone(), client.clone());
+        let result =
+            tokio::spawn(async move { panicking.dispatch(&task_client, text_item("1", 3)).await });
+        assert!(result.await.unwrap_err().is_panic());
+        assert_eq!(
+            dispatcher.dispatch(&client, text_item("1", 3)).await,
+            Flow::Stop
+        );
+        assert_eq!(
+            dispatcher.dispatch(&client, text_item("1", 3)).await,
+            Flow::Stop
+        );
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_dedup_capacity() {
+        let dedup = Dedup::new(2);
+        assert!(dedup.insert("1"));
+        assert!(dedup.insert("2"));
+        assert!(!dedup.insert("2"));
+        assert!(dedup.insert("3"));
+        assert!(dedup.insert("1"));
+    }
+}
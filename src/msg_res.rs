@@ -1,21 +1,28 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::send;
+
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 struct TextMsg {
     menu_id: String,
     content: String,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ImageMsg {
     media_id: String,
 }
+// 事件字段较多，但消息只在拉取时构造一次，不值得为此装箱
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Message {
     /// 文本消息
     Text {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         menu_id: Option<String>,
         content: String,
     },
@@ -50,8 +57,8 @@ pub enum Message {
         pagepath: String,
         thumb_media_id: String,
     },
-    /// TODO: 菜单消息
-    Msgmenu {},
+    /// 菜单消息，仅出现在客服发送的消息中
+    Msgmenu(send::Menu),
     /// 视频号产品消息
     ChannelsShopProduct {
         product_id: String,
@@ -70,16 +77,90 @@ pub enum Message {
         shop_nickname: String,
     },
     Event {
-        /// TODO: 修改为枚举
-        event_type: String,
+        event_type: KfEvent,
         open_kfid: String,
+        /// 接待人员接待状态变更事件不带该字段，此时为空
+        #[serde(default, skip_serializing_if = "String::is_empty")]
         external_userid: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         scene: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         scene_param: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         welcome_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        msg_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        recall_msgid: Option<String>,
+        /// 发送失败的消息ID，消息发送失败事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fail_msgid: Option<String>,
+        /// 失败类型，消息发送失败事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fail_type: Option<u32>,
+        /// 变更类型，会话状态变更事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        change_type: Option<u32>,
+        /// 原接待人员，会话状态变更事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old_servicer_userid: Option<String>,
+        /// 新接待人员，会话状态变更事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        new_servicer_userid: Option<String>,
+        /// 接待人员，接待状态变更、撤回消息和拒收客户消息变更事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        servicer_userid: Option<String>,
+        /// 接待人员的接待状态，1为接待中，2为停止接待
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<u32>,
+        /// 停止接待的子类型，0为停止接待，1为暂时挂起
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stop_type: Option<u32>,
+        /// 拒收客户消息，1为拒收，0为取消拒收
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reject_switch: Option<u32>,
+        /// 从视频号进入会话时的视频号信息，用户进入会话事件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wechat_channels: Option<WechatChannels>,
     },
 }
 
+/// 用户进入会话事件中的视频号信息
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WechatChannels {
+    /// 视频号名称，从视频号主页、直播间或视频进入会话时返回
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// 视频号小店名称，从小店商品或订单进入会话时返回
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shop_nickname: Option<String>,
+    /// 进入场景
+    pub scene: u32,
+}
+
+/// 事件类型
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KfEvent {
+    /// 用户进入会话事件
+    EnterSession,
+    /// 消息发送失败事件
+    MsgSendFail,
+    /// 接待人员接待状态变更事件
+    ServicerStatusChange,
+    /// 会话状态变更事件
+    SessionStatusChange,
+    /// 用户撤回消息事件
+    UserRecallMsg,
+    /// 接待人员撤回消息事件
+    ServicerRecallMsg,
+    /// 拒收客户消息变更事件
+    RejectCustomerMsgSwitchChange,
+    /// 未知事件
+    #[serde(other)]
+    Unknown,
+}
+
 impl Message {}
 
 /// 消息类型判断
@@ -93,7 +174,6 @@ impl Message {
         matches!(self, Message::Image { .. })
     }
     /// 判断消息是否为语音消息
-
     pub fn is_voice(&self) -> bool {
         matches!(self, Message::Voice { .. })
     }
@@ -129,21 +209,42 @@ impl Message {
     pub fn is_channels_shop_order(&self) -> bool {
         matches!(self, Message::ChannelsShopOrder { .. })
     }
+    /// 判断消息是否为事件
+    pub fn is_event(&self) -> bool {
+        matches!(self, Message::Event { .. })
+    }
+    /// 获取事件类型，消息不是事件时返回`None`
+    pub fn event_type(&self) -> Option<KfEvent> {
+        match self {
+            Message::Event { event_type, .. } => Some(*event_type),
+            _ => None,
+        }
+    }
+    /// 获取文本消息的内容，消息不是文本消息时返回`None`
+    pub fn text_content(&self) -> Option<&str> {
+        match self {
+            Message::Text { content, .. } => Some(content),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MsgItem {
     pub msgid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub open_kfid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub external_userid: Option<String>,
     pub send_time: u64,
     pub origin: MsgOrigin,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub servicer_userid: Option<String>,
     pub msgtype: String,
     #[serde(flatten)]
     pub message: Message,
 }
-#[derive(Deserialize_repr, Serialize_repr, Debug, Clone)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy, Eq, PartialEq)]
 #[repr(u8)]
 pub enum MsgOrigin {
     WeiXinCustomer = 3,
@@ -151,6 +252,61 @@ pub enum MsgOrigin {
     Kf = 5,
 }
 
+/// 回复消息错误类型
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReplyErr {
+    /// 消息不是由微信客户发送的
+    Origin(MsgOrigin),
+    /// 消息缺少external_userid
+    MissingExternalUserid,
+    /// 消息缺少open_kfid
+    MissingOpenKfid,
+    /// 回复内容未通过校验
+    Build(send::BuildErr),
+}
+
+impl std::fmt::Display for ReplyErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplyErr::Origin(origin) => write!(
+                f,
+                "only messages from WeChat customers can be replied to, got origin {origin:?}"
+            ),
+            ReplyErr::MissingExternalUserid => write!(f, "message has no external_userid"),
+            ReplyErr::MissingOpenKfid => write!(f, "message has no open_kfid"),
+            ReplyErr::Build(err) => write!(f, "invalid reply: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplyErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplyErr::Build(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl MsgItem {
+    /// 生成回复该消息的待发送消息，接收者为发送该消息的客户，回复内容按[`send::OutgoingMessage`]的规则校验
+    pub fn reply(&self, msgtype: send::MsgType) -> Result<send::Message, ReplyErr> {
+        if self.origin != MsgOrigin::WeiXinCustomer {
+            return Err(ReplyErr::Origin(self.origin));
+        }
+        let touser = self
+            .external_userid
+            .clone()
+            .ok_or(ReplyErr::MissingExternalUserid)?;
+        let open_kfid = self.open_kfid.clone().ok_or(ReplyErr::MissingOpenKfid)?;
+        send::OutgoingMessage::from(msgtype)
+            .to(touser)
+            .from_kf(open_kfid)
+            .build()
+            .map_err(ReplyErr::Build)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,6 +436,37 @@ mod tests {
         assert!(msg.message.is_miniprogram())
     }
 
+    #[test]
+    fn test_msgmenu_msg() {
+        let str = r#"
+           "msgtype" : "msgmenu",
+           "msgmenu" : {
+                "head_content": "您对本次服务是否满意呢? ",
+                "list": [
+                    { "type": "click", "click": { "id": "101", "content": "满意" } },
+                    { "type": "view", "view": { "url": "https://work.weixin.qq.com", "content": "查看" } },
+                    {
+                        "type": "miniprogram",
+                        "miniprogram": { "appid": "APPID", "pagepath": "PAGE_PATH", "content": "打开" }
+                    },
+                    { "type": "text", "text": { "content": "结束", "no_newline": 1 } }
+                ]
+           }
+        "#;
+        let msg = parse_msg_item(str).unwrap();
+        let Message::Msgmenu(menu) = msg.message else {
+            panic!("not a menu");
+        };
+        assert_eq!(
+            menu.head_content.as_deref(),
+            Some("您对本次服务是否满意呢? ")
+        );
+        assert!(menu.tail_content.is_none());
+        let names: Vec<_> = menu.list.iter().map(send::MenuItem::name).collect();
+        assert_eq!(names, ["click", "view", "miniprogram", "text"]);
+        assert!(matches!(&menu.list[3], send::MenuItem::Text(content, 1) if content == "结束"));
+    }
+
     #[test]
     fn test_channels_shop_product_msg() {
         let str = r#"
@@ -314,6 +501,39 @@ mod tests {
         assert!(msg.message.is_channels_shop_order())
     }
 
+    #[test]
+    fn test_reply() {
+        let str = r#"
+            "msgtype": "text",
+            "text": {
+                "content": "hello world"
+            }
+        "#;
+        let mut msg = parse_msg_item(str).unwrap();
+        let reply = msg.reply(send::MsgType::Text("hi".to_string())).unwrap();
+        assert_eq!(reply.touser, "wmAJ2GCAAAme1XQRC-NI-q0_ZM9ukoAw");
+        assert_eq!(reply.open_kfid, "wkAJ2GCAAASSm4_FhToWMFea0xAFfd3Q");
+
+        let content = "a".repeat(send::MAX_CONTENT_LEN + 1);
+        let err = msg.reply(send::MsgType::Text(content)).unwrap_err();
+        assert_eq!(
+            err,
+            ReplyErr::Build(send::BuildErr::ContentTooLong(send::MAX_CONTENT_LEN + 1))
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid reply: text content is 2049 bytes, at most 2048 allowed"
+        );
+
+        msg.external_userid = None;
+        let result = msg.reply(send::MsgType::Text("hi".to_string()));
+        assert_eq!(result.unwrap_err(), ReplyErr::MissingExternalUserid);
+
+        msg.origin = MsgOrigin::Kf;
+        let result = msg.reply(send::MsgType::Text("hi".to_string()));
+        assert_eq!(result.unwrap_err(), ReplyErr::Origin(MsgOrigin::Kf));
+    }
+
     fn parse_msg_item(str: &str) -> serde_json::Result<MsgItem> {
         let data = gen_data(str);
         from_str(&data)
@@ -1,8 +1,9 @@
 use quick_xml::de::from_str;
 use quick_xml::DeError;
 use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct WeiXinCallbackRes {
     pub to_user_name: String,
@@ -12,6 +13,46 @@ pub struct WeiXinCallbackRes {
     pub token: String,
     pub open_kf_id: String,
 }
+impl WeiXinCallbackRes {
+    /// 客服消息或事件的回调
+    pub fn new(to_user_name: &str, token: &str, open_kf_id: &str) -> Self {
+        let create_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            to_user_name: to_user_name.to_string(),
+            create_time: create_time.to_string(),
+            msg_type: "event".to_string(),
+            event: "kf_msg_or_event".to_string(),
+            token: token.to_string(),
+            open_kf_id: open_kf_id.to_string(),
+        }
+    }
+
+    /// 生成回调的XML
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<xml><ToUserName><![CDATA[{}]]></ToUserName><CreateTime>{}</CreateTime><MsgType><![CDATA[{}]]></MsgType><Event><![CDATA[{}]]></Event><Token><![CDATA[{}]]></Token><OpenKfId><![CDATA[{}]]></OpenKfId></xml>",
+            self.to_user_name, self.create_time, self.msg_type, self.event, self.token, self.open_kf_id
+        )
+    }
+}
+
+/// 回调中的token可用于拉取消息，`Debug`输出时隐去
+impl std::fmt::Debug for WeiXinCallbackRes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeiXinCallbackRes")
+            .field("to_user_name", &self.to_user_name)
+            .field("create_time", &self.create_time)
+            .field("msg_type", &self.msg_type)
+            .field("event", &self.event)
+            .field("token", &"***")
+            .field("open_kf_id", &self.open_kf_id)
+            .finish()
+    }
+}
+
 pub fn parse_callback_xml(xml: &str) -> Result<WeiXinCallbackRes, DeError> {
     from_str(xml)
 }
@@ -29,5 +70,7 @@ mod tests {
         assert_eq!(result.event, "kf_msg_or_event");
         assert_eq!(result.token, "world");
         assert_eq!(result.open_kf_id, "zhangsan");
+        assert_eq!(parse_callback_xml(&result.to_xml()).unwrap(), result);
+        assert!(!format!("{result:?}").contains("world"));
     }
 }
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::endpoint::Endpoint;
+
+/// 接口调用超过频率限制
+pub const API_FREQ_OUT_OF_LIMIT_ERRCODE: i32 = 45009;
+/// 接口并发调用超过限制
+pub const API_CONCURRENT_OUT_OF_LIMIT_ERRCODE: i32 = 45033;
+/// 触发退避的错误码
+pub const THROTTLE_ERRCODES: &[i32] = &[
+    API_FREQ_OUT_OF_LIMIT_ERRCODE,
+    API_CONCURRENT_OUT_OF_LIMIT_ERRCODE,
+];
+
+/// 默认限流的接口：收发消息和客服账号管理
+pub const DEFAULT_LIMITED_PATHS: &[&str] = &[
+    "/kf/send_msg",
+    "/kf/sync_msg",
+    "/kf/account/add",
+    "/kf/account/del",
+    "/kf/account/update",
+    "/kf/account/list",
+    "/kf/add_contact_way",
+];
+
+/// [`RateLimiter::wecom`]中每个客服账号每分钟最多发送的消息数
+///
+/// 企业微信没有公开按客服账号的发送频率，这是客户端的保守默认值，可用[`RateLimiter::limit_per_kf`]覆盖。
+pub const KF_SEND_MSG_PER_MINUTE: u32 = 1_000;
+/// [`RateLimiter::wecom`]中每个客服账号每分钟最多拉取消息的次数，同样是客户端的保守默认值
+pub const KF_SYNC_MSG_PER_MINUTE: u32 = 600;
+/// [`RateLimiter::wecom`]中每个客服账号同时进行的`sync_msg`请求数，
+/// 同一客服账号的拉取需要串行以保证游标顺序，并发拉取会返回45033
+pub const KF_SYNC_MSG_CONCURRENCY: usize = 1;
+
+/// 限流额度：每`period`最多`capacity`次请求，允许`capacity`次的突发
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Limit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl Limit {
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            period,
+        }
+    }
+
+    /// 每秒最多`n`次
+    pub fn per_second(n: u32) -> Self {
+        Self::new(n, Duration::from_secs(1))
+    }
+
+    /// 每分钟最多`n`次
+    pub fn per_minute(n: u32) -> Self {
+        Self::new(n, Duration::from_secs(60))
+    }
+
+    /// 每小时最多`n`次
+    pub fn per_hour(n: u32) -> Self {
+        Self::new(n, Duration::from_secs(3600))
+    }
+}
+
+/// 超出额度时的处理方式
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    /// 等待直到有可用额度
+    Queue,
+    /// 直接返回错误
+    FailFast,
+}
+
+/// 令牌桶
+#[derive(Debug)]
+struct Bucket {
+    limit: Limit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: Limit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.capacity as f64,
+            updated: now,
+        }
+    }
+
+    /// 补充令牌，返回获取一个令牌还需等待的时间
+    fn refill(&mut self, now: Instant) -> Duration {
+        let capacity = self.limit.capacity as f64;
+        let rate = capacity / self.limit.period.as_secs_f64();
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / rate)
+        }
+    }
+}
+
+/// 接口返回限流错误后的退避状态
+#[derive(Debug)]
+struct Penalty {
+    /// 连续返回限流错误的次数
+    count: u32,
+    until: Instant,
+}
+
+/// 桶的标识：接口路径、客服账号和额度的周期
+type Key = (String, Option<String>, Duration);
+
+/// 并发许可，请求完成前需要一直持有，丢弃时归还额度
+#[derive(Debug)]
+pub struct Permit {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// 客户端限流器
+///
+/// 按接口路径和按（接口路径，客服账号）分别维护令牌桶，请求需要同时从所有匹配的桶中取得令牌。
+/// 未配置的接口不限流，[`RateLimiter::wecom`]提供了按企业微信基础频率限制配置的默认额度。
+///
+/// 接口返回[`THROTTLE_ERRCODES`]中的错误码时，该接口暂停请求一段时间，
+/// 连续返回时退避时间加倍，直到请求成功。
+///
+/// 还可以用[`RateLimiter::concurrency_per_kf`]限制同一客服账号对某个接口的并发请求数。
+#[derive(Debug)]
+pub struct RateLimiter {
+    mode: Mode,
+    endpoints: HashMap<String, Vec<Limit>>,
+    per_kf: HashMap<String, Vec<Limit>>,
+    buckets: Mutex<HashMap<Key, Bucket>>,
+    concurrency: HashMap<String, usize>,
+    /// 按（接口路径，客服账号）分配的并发额度
+    slots: Mutex<HashMap<(String, String), Arc<Semaphore>>>,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    penalties: Mutex<HashMap<String, Penalty>>,
+}
+
+fn base_path(path: &str) -> &str {
+    path.split('?').next().unwrap_or_default()
+}
+
+impl RateLimiter {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            endpoints: HashMap::new(),
+            per_kf: HashMap::new(),
+            buckets: Mutex::default(),
+            concurrency: HashMap::new(),
+            slots: Mutex::default(),
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            penalties: Mutex::default(),
+        }
+    }
+
+    /// 按企业微信的频率限制配置默认额度
+    ///
+    /// - [`DEFAULT_LIMITED_PATHS`]中的接口：每个企业调用单个接口不超过1万次/分钟、15万次/小时
+    /// - 每个客服账号：发送消息[`KF_SEND_MSG_PER_MINUTE`]次/分钟，拉取消息[`KF_SYNC_MSG_PER_MINUTE`]次/分钟，
+    ///   同时最多[`KF_SYNC_MSG_CONCURRENCY`]个`sync_msg`请求
+    ///
+    /// 发送消息对每个客户的条数限制由[`crate::window::WindowTracker`]负责。
+    pub fn wecom(mode: Mode) -> Self {
+        DEFAULT_LIMITED_PATHS
+            .iter()
+            .fold(Self::new(mode), |limiter, path| {
+                limiter
+                    .limit(path, Limit::per_minute(10_000))
+                    .limit(path, Limit::per_hour(150_000))
+            })
+            .limit_per_kf("/kf/send_msg", Limit::per_minute(KF_SEND_MSG_PER_MINUTE))
+            .limit_per_kf("/kf/sync_msg", Limit::per_minute(KF_SYNC_MSG_PER_MINUTE))
+            .concurrency_per_kf("/kf/sync_msg", KF_SYNC_MSG_CONCURRENCY)
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// 限制接口的总请求频率，`path`为接口路径，如`/kf/send_msg`
+    ///
+    /// 同一接口可以设置多个周期不同的额度，同一周期的额度后设置的覆盖先设置的。
+    pub fn limit(mut self, path: &str, limit: Limit) -> Self {
+        insert(self.endpoints.entry(path.to_string()).or_default(), limit);
+        self
+    }
+
+    /// 限制接口对每个客服账号的请求频率
+    pub fn limit_per_kf(mut self, path: &str, limit: Limit) -> Self {
+        insert(self.per_kf.entry(path.to_string()).or_default(), limit);
+        self
+    }
+
+    /// 限制接口对每个客服账号的并发请求数，超出时按[`Mode`]等待或返回错误
+    pub fn concurrency_per_kf(mut self, path: &str, max: usize) -> Self {
+        self.concurrency.insert(path.to_string(), max.max(1));
+        self
+    }
+
+    /// 取得并发许可，接口没有并发限制或请求不属于客服账号时返回`None`
+    ///
+    /// 排队模式下等待其他请求归还许可；快速失败模式下返回`Err(Duration::ZERO)`，表示无法预估等待时间。
+    pub async fn enter<E: Endpoint>(&self, endpoint: &E) -> Result<Option<Permit>, Duration> {
+        let Some(semaphore) = self.semaphore(endpoint) else {
+            return Ok(None);
+        };
+        let permit = match self.mode {
+            Mode::Queue => semaphore.acquire_owned().await.ok(),
+            Mode::FailFast => semaphore.try_acquire_owned().ok(),
+        };
+        permit
+            .map(|permit| Some(Permit { _permit: permit }))
+            .ok_or(Duration::ZERO)
+    }
+
+    fn semaphore<E: Endpoint>(&self, endpoint: &E) -> Option<Arc<Semaphore>> {
+        let path = endpoint.path();
+        let path = base_path(&path);
+        let max = *self.concurrency.get(path)?;
+        let open_kfid = endpoint.open_kfid()?;
+        let mut slots = self.slots.lock().unwrap();
+        let key = (path.to_string(), open_kfid.to_string());
+        Some(
+            slots
+                .entry(key)
+                .or_insert_with(|| Arc::new(Semaphore::new(max)))
+                .clone(),
+        )
+    }
+
+    /// 设置接口返回限流错误后的退避时间，第n次连续返回时等待`base * 2^(n-1)`，最长不超过`max`
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_backoff = base;
+        self.max_backoff = max.max(base);
+        self
+    }
+
+    /// 记录接口返回的错误码，限流错误使该接口进入退避，其他结果结束退避
+    pub fn record<E: Endpoint>(&self, endpoint: &E, errcode: Option<i32>) {
+        self.record_at(&endpoint.path(), errcode, Instant::now())
+    }
+
+    fn record_at(&self, path: &str, errcode: Option<i32>, now: Instant) {
+        let path = base_path(path);
+        let mut penalties = self.penalties.lock().unwrap();
+        if !errcode.is_some_and(|errcode| THROTTLE_ERRCODES.contains(&errcode)) {
+            penalties.remove(path);
+            return;
+        }
+        let penalty = penalties.entry(path.to_string()).or_insert(Penalty {
+            count: 0,
+            until: now,
+        });
+        penalty.count += 1;
+        let delay = self
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(penalty.count - 1))
+            .min(self.max_backoff);
+        penalty.until = now + delay;
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            endpoint = path,
+            errcode,
+            delay_ms = delay.as_millis() as u64,
+            "throttled by server"
+        );
+    }
+
+    /// 尝试为请求取得额度，额度不足时不消耗任何令牌并返回需要等待的时间
+    pub fn try_acquire<E: Endpoint>(&self, endpoint: &E) -> Result<(), Duration> {
+        self.try_acquire_at(endpoint, Instant::now())
+    }
+
+    fn try_acquire_at<E: Endpoint>(&self, endpoint: &E, now: Instant) -> Result<(), Duration> {
+        let path = endpoint.path();
+        let path = base_path(&path);
+        if let Some(penalty) = self.penalties.lock().unwrap().get(path) {
+            if penalty.until > now {
+                return Err(penalty.until - now);
+            }
+        }
+        let mut keys = vec![];
+        for limit in self.endpoints.get(path).into_iter().flatten() {
+            keys.push(((path.to_string(), None, limit.period), *limit));
+        }
+        if let Some(open_kfid) = endpoint.open_kfid() {
+            for limit in self.per_kf.get(path).into_iter().flatten() {
+                let key = (path.to_string(), Some(open_kfid.to_string()), limit.period);
+                keys.push((key, *limit));
+            }
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut wait = Duration::ZERO;
+        for (key, limit) in &keys {
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| Bucket::new(*limit, now));
+            wait = wait.max(bucket.refill(now));
+        }
+        if wait > Duration::ZERO {
+            return Err(wait);
+        }
+        for (key, _) in &keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// 取得额度，排队模式下等待直到有可用额度，快速失败模式下返回需要等待的时间
+    pub async fn acquire<E: Endpoint>(&self, endpoint: &E) -> Result<(), Duration> {
+        loop {
+            match self.try_acquire(endpoint) {
+                Err(wait) if self.mode == Mode::Queue => tokio::time::sleep(wait).await,
+                result => return result,
+            }
+        }
+    }
+}
+
+/// 添加额度，替换周期相同的额度
+fn insert(limits: &mut Vec<Limit>, limit: Limit) {
+    limits.retain(|l| l.period != limit.period);
+    limits.push(limit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receive::SyncMsg;
+
+    fn sync_msg(open_kfid: &str) -> SyncMsg {
+        SyncMsg {
+            open_kfid: Some(open_kfid.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_endpoint_limit() {
+        let limiter = RateLimiter::new(Mode::FailFast).limit("/kf/sync_msg", Limit::per_second(2));
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), now), Ok(()));
+        assert_eq!(limiter.try_acquire_at(&sync_msg("B"), now), Ok(()));
+        let wait = limiter.try_acquire_at(&sync_msg("A"), now).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        let later = now + Duration::from_millis(500);
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), later), Ok(()));
+        assert!(limiter.try_acquire_at(&sync_msg("A"), later).is_err());
+    }
+
+    #[test]
+    fn test_per_kf_limit() {
+        let limiter = RateLimiter::new(Mode::FailFast)
+            .limit("/kf/sync_msg", Limit::per_second(3))
+            .limit_per_kf("/kf/sync_msg", Limit::per_second(1));
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), now), Ok(()));
+        assert!(limiter.try_acquire_at(&sync_msg("A"), now).is_err());
+        assert_eq!(limiter.try_acquire_at(&sync_msg("B"), now), Ok(()));
+        assert_eq!(limiter.try_acquire_at(&sync_msg("C"), now), Ok(()));
+        // 总额度已用完，拒绝时不消耗客服账号的额度
+        assert!(limiter.try_acquire_at(&sync_msg("D"), now).is_err());
+        let later = now + Duration::from_millis(334);
+        assert_eq!(limiter.try_acquire_at(&sync_msg("D"), later), Ok(()));
+    }
+
+    #[test]
+    fn test_unlimited_endpoint() {
+        let limiter = RateLimiter::new(Mode::FailFast).limit("/kf/send_msg", Limit::per_second(1));
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(limiter.try_acquire_at(&sync_msg("A"), now), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_multiple_periods() {
+        let limiter = RateLimiter::new(Mode::FailFast)
+            .limit("/kf/sync_msg", Limit::per_second(2))
+            .limit("/kf/sync_msg", Limit::per_minute(3));
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), now), Ok(()));
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), now), Ok(()));
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), later), Ok(()));
+        let wait = limiter.try_acquire_at(&sync_msg("A"), later).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(19));
+    }
+
+    #[test]
+    fn test_wecom_defaults() {
+        let limiter = RateLimiter::wecom(Mode::FailFast);
+        for path in DEFAULT_LIMITED_PATHS {
+            let periods: Vec<_> = limiter.endpoints[*path].iter().map(|l| l.period).collect();
+            assert_eq!(
+                periods,
+                [Duration::from_secs(60), Duration::from_secs(3600)]
+            );
+        }
+        assert_eq!(
+            limiter.per_kf["/kf/send_msg"],
+            [Limit::per_minute(KF_SEND_MSG_PER_MINUTE)]
+        );
+        assert_eq!(
+            limiter.per_kf["/kf/sync_msg"],
+            [Limit::per_minute(KF_SYNC_MSG_PER_MINUTE)]
+        );
+        assert_eq!(limiter.concurrency["/kf/sync_msg"], KF_SYNC_MSG_CONCURRENCY);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_fail_fast() {
+        let limiter = RateLimiter::new(Mode::FailFast).concurrency_per_kf("/kf/sync_msg", 1);
+        let permit = limiter.enter(&sync_msg("A")).await.unwrap();
+        assert!(permit.is_some());
+        assert_eq!(
+            limiter.enter(&sync_msg("A")).await.unwrap_err(),
+            Duration::ZERO
+        );
+        assert!(limiter.enter(&sync_msg("B")).await.unwrap().is_some());
+        drop(permit);
+        assert!(limiter.enter(&sync_msg("A")).await.unwrap().is_some());
+        assert!(limiter.enter(&SyncMsg::default()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_queue() {
+        let limiter = Arc::new(RateLimiter::new(Mode::Queue).concurrency_per_kf("/kf/sync_msg", 1));
+        let permit = limiter.enter(&sync_msg("A")).await.unwrap();
+        let waiting = limiter.clone();
+        let task =
+            tokio::spawn(async move { waiting.enter(&sync_msg("A")).await.unwrap().is_some() });
+        tokio::task::yield_now().await;
+        assert!(!task.is_finished());
+        drop(permit);
+        assert!(task.await.unwrap());
+    }
+
+    #[test]
+    fn test_throttle_backoff() {
+        let limiter = RateLimiter::new(Mode::FailFast)
+            .backoff(Duration::from_secs(1), Duration::from_secs(3));
+        let now = Instant::now();
+        limiter.record_at("/kf/sync_msg", Some(API_FREQ_OUT_OF_LIMIT_ERRCODE), now);
+        let wait = limiter.try_acquire_at(&sync_msg("A"), now).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), later), Ok(()));
+        limiter.record_at(
+            "/kf/sync_msg",
+            Some(API_CONCURRENT_OUT_OF_LIMIT_ERRCODE),
+            later,
+        );
+        let wait = limiter.try_acquire_at(&sync_msg("A"), later).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(2));
+        limiter.record_at("/kf/sync_msg", Some(API_FREQ_OUT_OF_LIMIT_ERRCODE), later);
+        let wait = limiter.try_acquire_at(&sync_msg("A"), later).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(3));
+
+        limiter.record_at("/kf/sync_msg", Some(0), later);
+        assert_eq!(limiter.try_acquire_at(&sync_msg("A"), later), Ok(()));
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::Instant;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer};
+
+use crate::constant::API_URL;
+use crate::endpoint::Endpoint;
+use crate::transport::{HttpRequest, Method};
+
+/// 转换为reqwest请求所需的各部分，异步和同步客户端共用
+pub(crate) struct Parts {
+    pub(crate) method: reqwest::Method,
+    pub(crate) url: String,
+    pub(crate) headers: HeaderMap,
+    /// GET请求没有请求体
+    pub(crate) body: Option<Vec<u8>>,
+}
+
+impl From<HttpRequest> for Parts {
+    fn from(req: HttpRequest) -> Self {
+        let headers = req
+            .headers
+            .iter()
+            .filter_map(|(k, v)| {
+                let name = HeaderName::from_bytes(k.as_bytes()).ok()?;
+                Some((name, HeaderValue::from_str(v).ok()?))
+            })
+            .collect();
+        let (method, body) = match req.method {
+            Method::Get => (reqwest::Method::GET, None),
+            Method::Post => (reqwest::Method::POST, Some(req.body)),
+        };
+        Self {
+            method,
+            url: req.url,
+            headers,
+            body,
+        }
+    }
+}
+
+pub(crate) fn to_reqwest(client: &reqwest::Client, req: HttpRequest) -> reqwest::RequestBuilder {
+    let parts = Parts::from(req);
+    let builder = client
+        .request(parts.method, parts.url)
+        .headers(parts.headers);
+    match parts.body {
+        Some(body) => builder.body(body),
+        None => builder,
+    }
+}
+
+/// 解析后的响应和其中的errcode
+struct Traced<R> {
+    res: R,
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    errcode: Option<i32>,
+}
+
+impl<'de, R: DeserializeOwned> Deserialize<'de> for Traced<R> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let errcode = value
+            .get("errcode")
+            .and_then(serde_json::Value::as_i64)
+            .and_then(|errcode| i32::try_from(errcode).ok());
+        let res = R::deserialize(value).map_err(D::Error::custom)?;
+        Ok(Self { res, errcode })
+    }
+}
+
+/// 与[`Client::call`](crate::Client::call)相同的span，记录接口、客服账号、errcode和耗时
+struct Span {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    start: Instant,
+}
+
+impl Span {
+    fn new<E: Endpoint>(endpoint: &E) -> Self {
+        #[cfg(feature = "tracing")]
+        let span = {
+            let path = endpoint.path();
+            tracing::info_span!(
+                "kf_wx.call",
+                endpoint = path.split('?').next().unwrap_or_default(),
+                open_kfid = endpoint.open_kfid(),
+                errcode = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+            )
+        };
+        #[cfg(not(feature = "tracing"))]
+        let _ = endpoint;
+        Self {
+            #[cfg(feature = "tracing")]
+            span,
+            start: Instant::now(),
+        }
+    }
+
+    /// 记录结果，错误信息已隐去URL
+    fn finish<R>(self, result: reqwest::Result<Traced<R>>) -> reqwest::Result<R> {
+        let result = result.map_err(reqwest::Error::without_url);
+        #[cfg(feature = "tracing")]
+        {
+            let span = self.span;
+            span.record("latency_ms", self.start.elapsed().as_millis() as u64);
+            match &result {
+                Ok(Traced {
+                    errcode: Some(errcode),
+                    ..
+                }) => {
+                    span.record("errcode", errcode);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    let error = crate::secret::redact(&err.to_string());
+                    tracing::warn!(parent: &span, %error, "api call failed");
+                }
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = self.start;
+        result.map(|traced| traced.res)
+    }
+}
+
+/// 使用reqwest请求接口并解析JSON响应
+pub(crate) async fn execute<E: Endpoint>(
+    endpoint: &E,
+    token: &str,
+) -> reqwest::Result<E::Response> {
+    let span = Span::new(endpoint);
+    let client = reqwest::Client::new();
+    let req = endpoint.to_request(API_URL, token);
+    let future = async { to_reqwest(&client, req).send().await?.json().await };
+    #[cfg(feature = "tracing")]
+    let future = tracing::Instrument::instrument(future, span.span.clone());
+    span.finish(future.await)
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) mod blocking {
+    use super::{Parts, Span};
+    use crate::constant::API_URL;
+    use crate::endpoint::Endpoint;
+    use crate::transport::HttpRequest;
+
+    fn to_reqwest(
+        client: &reqwest::blocking::Client,
+        req: HttpRequest,
+    ) -> reqwest::blocking::RequestBuilder {
+        let parts = Parts::from(req);
+        let builder = client
+            .request(parts.method, parts.url)
+            .headers(parts.headers);
+        match parts.body {
+            Some(body) => builder.body(body),
+            None => builder,
+        }
+    }
+
+    pub(crate) fn execute<E: Endpoint>(endpoint: &E, token: &str) -> reqwest::Result<E::Response> {
+        let span = Span::new(endpoint);
+        let client = reqwest::blocking::Client::new();
+        let req = endpoint.to_request(API_URL, token);
+        let result = {
+            #[cfg(feature = "tracing")]
+            let _entered = span.span.enter();
+            to_reqwest(&client, req).send().and_then(|res| res.json())
+        };
+        span.finish(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        let req = HttpRequest {
+            method: Method::Post,
+            url: "https://qyapi.weixin.qq.com/cgi-bin/kf/send_msg?access_token=TOKEN".to_string(),
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: b"{}".to_vec(),
+        };
+        let parts = Parts::from(req.clone());
+        assert_eq!(parts.method, reqwest::Method::POST);
+        assert_eq!(parts.url, req.url);
+        assert_eq!(parts.headers["content-type"], "application/json");
+        assert_eq!(parts.body.as_deref(), Some(b"{}".as_slice()));
+
+        let built = to_reqwest(&reqwest::Client::new(), req).build().unwrap();
+        assert_eq!(built.method(), reqwest::Method::POST);
+        assert_eq!(built.headers()["content-type"], "application/json");
+        assert_eq!(
+            built.body().and_then(|body| body.as_bytes()),
+            Some(b"{}".as_slice())
+        );
+
+        let req = HttpRequest {
+            method: Method::Get,
+            url: "https://qyapi.weixin.qq.com/cgi-bin/gettoken".to_string(),
+            headers: vec![],
+            body: vec![],
+        };
+        let parts = Parts::from(req);
+        assert_eq!(parts.method, reqwest::Method::GET);
+        assert!(parts.body.is_none());
+    }
+
+    #[test]
+    fn test_traced() {
+        #[derive(Deserialize)]
+        struct Res {
+            errcode: i32,
+            errmsg: String,
+        }
+
+        let traced: Traced<Res> = serde_json::from_str(r#"{"errcode":0,"errmsg":"ok"}"#).unwrap();
+        assert_eq!(traced.errcode, Some(0));
+        assert_eq!((traced.res.errcode, traced.res.errmsg.as_str()), (0, "ok"));
+
+        let traced: Traced<serde_json::Value> = serde_json::from_str(r#"{"data":1}"#).unwrap();
+        assert_eq!(traced.errcode, None);
+        assert!(serde_json::from_str::<Traced<Res>>(r#"{"errcode":"x"}"#).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::transport::{HttpResponse, TransportErr};
+
+/// 系统繁忙，服务端此时可能已经处理了请求
+pub const SYSTEM_BUSY_ERRCODE: i32 = -1;
+
+#[derive(Deserialize)]
+struct ErrCode {
+    #[serde(default)]
+    errcode: i32,
+}
+
+/// 读取响应中的errcode
+pub(crate) fn errcode(body: &[u8]) -> Option<i32> {
+    serde_json::from_slice::<ErrCode>(body)
+        .ok()
+        .map(|res| res.errcode)
+}
+
+/// 0到1之间的随机数
+fn random_fraction() -> f64 {
+    let value = RandomState::new().build_hasher().finish();
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 请求失败时的重试策略
+///
+/// 连接失败和HTTP 429时请求未被处理，总是可以重试；超时、5xx和可重试的errcode
+/// 只在请求幂等时重试，避免重复执行非幂等的请求（如未指定msgid的发送消息）。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    errcodes: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            errcodes: vec![SYSTEM_BUSY_ERRCODE],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// 设置最多请求次数（包括第一次请求）
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// 设置退避时间，第n次重试前等待`base * 2^(n-1)`，最长不超过`max`
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = max.max(base);
+        self
+    }
+
+    /// 是否在退避时间上增加随机抖动
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// 添加可重试的errcode
+    pub fn retry_errcode(mut self, errcode: i32) -> Self {
+        if !self.errcodes.contains(&errcode) {
+            self.errcodes.push(errcode);
+        }
+        self
+    }
+
+    /// 第`attempt`次请求失败后，是否需要再次请求
+    pub fn should_retry(
+        &self,
+        attempt: u32,
+        result: &Result<HttpResponse, TransportErr>,
+        idempotent: bool,
+    ) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match result {
+            Err(TransportErr::Connect(_)) => true,
+            Err(_) => idempotent,
+            Ok(res) if res.status == 429 => true,
+            Ok(res) if res.status >= 500 => idempotent,
+            Ok(res) => {
+                idempotent && errcode(&res.body).is_some_and(|code| self.errcodes.contains(&code))
+            }
+        }
+    }
+
+    /// 第`attempt`次请求失败后，再次请求前的等待时间
+    ///
+    /// 开启抖动时在退避时间的一半到全部之间随机取值，避免大量客户端同时重试。
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter {
+            delay / 2 + (delay / 2).mul_f64(random_fraction())
+        } else {
+            delay
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, body: &str) -> Result<HttpResponse, TransportErr> {
+        let mut res = HttpResponse::ok(body);
+        res.status = status;
+        Ok(res)
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+        let busy = response(200, r#"{"errcode":-1,"errmsg":"system busy"}"#);
+        let timeout = Err(TransportErr::Timeout("timeout".to_string()));
+        let connect = Err(TransportErr::Connect("refused".to_string()));
+
+        assert!(policy.should_retry(1, &busy, true));
+        assert!(!policy.should_retry(1, &busy, false));
+        assert!(policy.should_retry(2, &timeout, true));
+        assert!(!policy.should_retry(3, &timeout, true));
+        assert!(policy.should_retry(1, &connect, false));
+        assert!(policy.should_retry(1, &response(429, ""), false));
+        assert!(policy.should_retry(1, &response(502, ""), true));
+        assert!(!policy.should_retry(1, &response(502, ""), false));
+        assert!(!policy.should_retry(1, &response(404, ""), true));
+        assert!(!policy.should_retry(1, &response(200, r#"{"errcode":0}"#), true));
+
+        let invalid = response(200, r#"{"errcode":40014}"#);
+        assert!(!policy.should_retry(1, &invalid, true));
+        assert!(policy.retry_errcode(40014).should_retry(1, &invalid, true));
+        assert!(!RetryPolicy::none().should_retry(1, &connect, true));
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::default()
+            .backoff(Duration::from_millis(100), Duration::from_millis(350))
+            .jitter(false);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(350));
+        assert_eq!(policy.delay(40), Duration::from_millis(350));
+
+        let delay = policy.jitter(true).delay(2);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// 敏感值的包装，`Debug`和`Display`只输出`***`，需要通过[`Secret::expose`]显式取值
+///
+/// 用于access_token、应用Secret、回调Token和EncodingAESKey，避免它们出现在日志和panic信息中。
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// 取出原始值
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+/// 隐去文本（如URL、错误信息）中的access_token和corpsecret参数值，以及JSON请求体中的token
+pub fn redact(text: &str) -> String {
+    static PARAMS: OnceLock<Regex> = OnceLock::new();
+    static FIELDS: OnceLock<Regex> = OnceLock::new();
+    let params = PARAMS
+        .get_or_init(|| Regex::new(r"(access_token|corpsecret)=[\w.~%-]*").expect("valid regex"));
+    let fields =
+        FIELDS.get_or_init(|| Regex::new(r#""token"\s*:\s*"[^"]*""#).expect("valid regex"));
+    let text = params.replace_all(text, "$1=***");
+    fields.replace_all(&text, r#""token":"***""#).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret() {
+        let secret = Secret::from("TOKEN");
+        assert_eq!(format!("{secret:?} {secret}"), "*** ***");
+        assert_eq!(secret.expose(), "TOKEN");
+
+        let secret: Secret<String> = serde_json::from_str(r#""TOKEN""#).unwrap();
+        assert_eq!(secret.expose(), "TOKEN");
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("error sending request for url (https://qyapi.weixin.qq.com/cgi-bin/kf/send_msg?access_token=TOKEN): timed out"),
+            "error sending request for url (https://qyapi.weixin.qq.com/cgi-bin/kf/send_msg?access_token=***): timed out"
+        );
+        assert_eq!(
+            redact("/gettoken?corpid=ID&corpsecret=SECRET"),
+            "/gettoken?corpid=ID&corpsecret=***"
+        );
+        assert_eq!(
+            redact(r#"{"cursor":"C","token": "SYNC_TOKEN"}"#),
+            r#"{"cursor":"C","token":"***"}"#
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::endpoint::{with_query, Endpoint};
+use crate::transport::Method;
+
+/// 添加或删除接待人员的请求
+#[derive(Debug, Clone, Serialize)]
+pub struct ServicerReq {
+    /// 客服账号ID
+    pub open_kfid: String,
+    /// 接待人员的userid，单次最多100个
+    pub userid_list: Vec<String>,
+}
+
+impl ServicerReq {
+    /// 创建添加或删除接待人员的请求
+    pub fn new(open_kfid: &str, userid_list: &[&str]) -> Self {
+        Self {
+            open_kfid: open_kfid.to_string(),
+            userid_list: userid_list
+                .iter()
+                .map(|userid| userid.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// 单个接待人员的操作结果，部分接待人员失败时整体`errcode`仍为0
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServicerResult {
+    pub userid: String,
+    pub errcode: i32,
+    pub errmsg: String,
+}
+
+/// 添加或删除接待人员的响应
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServicerRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    #[serde(default)]
+    pub result_list: Vec<ServicerResult>,
+}
+
+/// 添加接待人员
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct AddServicer(pub ServicerReq);
+
+/// 删除接待人员
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct DelServicer(pub ServicerReq);
+
+/// 接待人员的接待状态
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ServicerStatus {
+    /// 接待中
+    Receiving = 0,
+    /// 停止接待
+    Stopped = 1,
+}
+
+/// 接待人员
+#[derive(Debug, Clone, Deserialize)]
+pub struct Servicer {
+    pub userid: String,
+    pub status: ServicerStatus,
+}
+
+/// 获取接待人员列表的响应
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServicerListRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    #[serde(default)]
+    pub servicer_list: Vec<Servicer>,
+}
+
+/// 获取接待人员列表
+#[derive(Debug, Clone)]
+pub struct ListServicer {
+    pub open_kfid: String,
+}
+
+impl ListServicer {
+    /// 获取指定客服账号的接待人员列表
+    pub fn new(open_kfid: &str) -> Self {
+        Self {
+            open_kfid: open_kfid.to_string(),
+        }
+    }
+}
+
+impl Endpoint for ListServicer {
+    type Response = ServicerListRes;
+
+    fn method(&self) -> Method {
+        Method::Get
+    }
+
+    fn path(&self) -> String {
+        with_query("/kf/servicer/list", &[("open_kfid", &self.open_kfid)])
+    }
+
+    fn body(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn open_kfid(&self) -> Option<&str> {
+        Some(&self.open_kfid)
+    }
+}
+
+json_endpoint!(
+    AddServicer,
+    "/kf/servicer/add",
+    ServicerRes,
+    open_kfid = |req| Some(req.0.open_kfid.as_str())
+);
+json_endpoint!(
+    DelServicer,
+    "/kf/servicer/del",
+    ServicerRes,
+    open_kfid = |req| Some(req.0.open_kfid.as_str())
+);
+
+api! {
+    /// 添加接待人员，每个客服账号目前最多可添加2000个接待人员
+    fn add(token: &str, req: &ServicerReq) -> ServicerRes = AddServicer(req.clone());
+    /// 删除接待人员
+    fn del(token: &str, req: &ServicerReq) -> ServicerRes = DelServicer(req.clone());
+    /// 获取客服账号的接待人员列表
+    fn list(token: &str, open_kfid: &str) -> ServicerListRes = ListServicer::new(open_kfid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_slice, from_str, json, Value};
+
+    #[test]
+    fn test_endpoints() {
+        let req = AddServicer(ServicerReq::new("OPEN_KFID", &["zhangsan", "lisi"]))
+            .to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert!(req.url.ends_with("/kf/servicer/add?access_token=TOKEN"));
+        let body: Value = from_slice(&req.body).unwrap();
+        let expected = json!({"open_kfid": "OPEN_KFID", "userid_list": ["zhangsan", "lisi"]});
+        assert_eq!(body, expected);
+
+        let req = ListServicer::new("OPEN_KFID")
+            .to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(req.method, Method::Get);
+        assert!(req
+            .url
+            .ends_with("/kf/servicer/list?open_kfid=OPEN_KFID&access_token=TOKEN"));
+    }
+
+    #[test]
+    fn test_del_servicer() {
+        let req = DelServicer(ServicerReq::new("OPEN_KFID", &["zhangsan"]));
+        assert_eq!(req.open_kfid(), Some("OPEN_KFID"));
+        let req = req.to_request("https://qyapi.weixin.qq.com/cgi-bin", "TOKEN");
+        assert_eq!(req.method, Method::Post);
+        assert!(req.url.ends_with("/kf/servicer/del?access_token=TOKEN"));
+        let body: Value = from_slice(&req.body).unwrap();
+        assert_eq!(
+            body,
+            json!({"open_kfid": "OPEN_KFID", "userid_list": ["zhangsan"]})
+        );
+    }
+
+    #[test]
+    fn test_servicer_res() {
+        let str = r#"{
+            "errcode": 0,
+            "errmsg": "success",
+            "result_list": [
+                { "userid": "zhangsan", "errcode": 0, "errmsg": "success" },
+                { "userid": "lisi", "errcode": 60111, "errmsg": "userid not found" }
+            ]
+        }"#;
+        let res: ServicerRes = from_str(str).unwrap();
+        assert_eq!(res.errcode, 0);
+        let failed: Vec<&str> = res
+            .result_list
+            .iter()
+            .filter(|result| result.errcode != 0)
+            .map(|result| result.userid.as_str())
+            .collect();
+        assert_eq!(failed, ["lisi"]);
+
+        let res: ServicerRes =
+            from_str(r#"{"errcode": 95000, "errmsg": "invalid open_kfid"}"#).unwrap();
+        assert!(res.result_list.is_empty());
+    }
+
+    #[test]
+    fn test_list_res() {
+        let str = r#"{
+            "errcode": 0,
+            "errmsg": "ok",
+            "servicer_list": [
+                { "userid": "zhangsan", "status": 0 },
+                { "userid": "lisi", "status": 1 }
+            ]
+        }"#;
+        let res: ServicerListRes = from_str(str).unwrap();
+        assert_eq!(res.servicer_list[1].status, ServicerStatus::Stopped);
+    }
+}
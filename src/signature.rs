@@ -1,10 +1,11 @@
 use hex::encode;
 use sha1::{Digest, Sha1};
-use std::vec;
+
+use crate::secret::Secret;
 
 #[derive(Debug, Clone)]
 pub struct Signature {
-    pub token: String,
+    pub token: Secret<String>,
     pub timestamp: String,
     pub nonce: String,
     pub echostr: String,
@@ -13,7 +14,7 @@ pub struct Signature {
 impl Signature {
     pub fn new(token: &str, timestamp: &str, nonce: &str, echostr: &str) -> Self {
         Signature {
-            token: token.to_string(),
+            token: Secret::from(token),
             timestamp: timestamp.to_string(),
             nonce: nonce.to_string(),
             echostr: echostr.to_string(),
@@ -23,8 +24,8 @@ impl Signature {
 
 pub fn msg_signature(signature: &Signature) -> String {
     let signature = signature.clone();
-    let mut arr = vec![
-        signature.token,
+    let mut arr = [
+        signature.token.expose().clone(),
         signature.timestamp,
         signature.nonce,
         signature.echostr,
@@ -37,9 +38,31 @@ pub fn msg_signature(signature: &Signature) -> String {
     encode(result)
 }
 
+/// 以恒定时间比较签名，耗时与第一个不同字符的位置无关
+pub fn signature_eq(signature: &str, expected: &str) -> bool {
+    if signature.len() != expected.len() {
+        return false;
+    }
+    signature
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+
+    #[test]
+    fn test_signature_eq() {
+        let signature = "477715d11cdb4164915debcba66cb864d751f3e6";
+        assert!(signature_eq(signature, signature));
+        assert!(!signature_eq(signature, &"0".repeat(40)));
+        assert!(!signature_eq(signature, &signature[..39]));
+        assert!(!signature_eq(signature, ""));
+    }
+
     #[test]
     fn test_msg_signature() {
         let token = "QDG6eK";
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::task::JoinHandle;
+
+use crate::callback::EventSink;
+use crate::client::{Client, ClientErr};
+use crate::dispatcher::Dispatcher;
+use crate::parse::WeiXinCallbackRes;
+use crate::receive::{MoreMsg, SyncMsg};
+
+/// 每次拉取的消息条数上限
+pub const SYNC_LIMIT: i32 = 1000;
+
+/// 回调中表示有新消息或事件的事件类型
+pub const KF_MSG_OR_EVENT: &str = "kf_msg_or_event";
+
+/// 回调驱动的拉取失败时，同一个回调token最多尝试的次数
+const SYNC_ATTEMPTS: u32 = 5;
+/// 拉取失败后第一次重试前的等待时间，之后每次加倍
+const SYNC_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// 拉取消息的游标存储，保证重启后从上次的位置继续拉取
+pub trait CursorStore: Send + Sync + 'static {
+    fn get(&self, open_kfid: &str) -> Option<String>;
+    fn set(&self, open_kfid: &str, cursor: &str);
+}
+
+/// 内存中的游标存储
+#[derive(Debug, Default)]
+pub struct MemoryCursorStore {
+    cursors: Mutex<HashMap<String, String>>,
+}
+
+impl CursorStore for MemoryCursorStore {
+    fn get(&self, open_kfid: &str) -> Option<String> {
+        self.cursors.lock().unwrap().get(open_kfid).cloned()
+    }
+
+    fn set(&self, open_kfid: &str, cursor: &str) {
+        let mut cursors = self.cursors.lock().unwrap();
+        cursors.insert(open_kfid.to_string(), cursor.to_string());
+    }
+}
+
+/// 拉取客服账号的全部新消息并交给分发器，返回拉取到的消息数
+///
+/// 每一页消息分发完成后才保存游标，中途失败时下次会从未处理完的页继续拉取。
+/// 处理函数panic时跳过该消息，避免同一页消息被反复拉取并再次panic。
+pub async fn pull(
+    client: &Client,
+    store: &dyn CursorStore,
+    dispatcher: &Dispatcher,
+    open_kfid: &str,
+    token: Option<&str>,
+) -> Result<usize, ClientErr> {
+    let mut count = 0;
+    loop {
+        let (page, has_more) = pull_page(client, store, dispatcher, open_kfid, token).await?;
+        count += page;
+        if has_more == MoreMsg::No {
+            return Ok(count);
+        }
+    }
+}
+
+/// 拉取一页消息并交给分发器，返回这一页的消息数和是否还有更多消息
+async fn pull_page(
+    client: &Client,
+    store: &dyn CursorStore,
+    dispatcher: &Dispatcher,
+    open_kfid: &str,
+    token: Option<&str>,
+) -> Result<(usize, MoreMsg), ClientErr> {
+    let msg = SyncMsg {
+        cursor: store.get(open_kfid),
+        token: token.map(str::to_string),
+        limit: Some(SYNC_LIMIT),
+        voice_format: None,
+        open_kfid: Some(open_kfid.to_string()),
+    };
+    let res = client.sync_msg(&msg).await?;
+    if res.errcode != 0 {
+        return Err(ClientErr::Api(res.errcode, res.errmsg));
+    }
+    let count = res.msg_list.len();
+    if let Some(metrics) = client.metrics_sink() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        for item in &res.msg_list {
+            let lag = Duration::from_secs(now.saturating_sub(item.send_time));
+            metrics.sync_lag(open_kfid, lag);
+        }
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        open_kfid,
+        messages = count,
+        has_more = res.has_more == MoreMsg::Yes,
+        "pulled messages"
+    );
+    for item in res.msg_list {
+        let msgid = item.msgid.clone();
+        let dispatch = Box::pin(dispatcher.dispatch(client, item));
+        if CatchUnwind(dispatch).await.is_err() {
+            #[cfg(feature = "tracing")]
+            tracing::error!(open_kfid, msgid, "handler panicked, message skipped");
+            #[cfg(not(feature = "tracing"))]
+            let _ = msgid;
+        }
+    }
+    if !res.next_cursor.is_empty() {
+        store.set(open_kfid, &res.next_cursor);
+    }
+    Ok((count, res.has_more))
+}
+
+/// 捕获future执行中的panic
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+        match catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+type ErrorHandler = Arc<dyn Fn(&str, ClientErr) + Send + Sync>;
+
+/// 客服账号的拉取状态
+#[derive(Debug, Default)]
+struct Account {
+    running: bool,
+    /// 等待拉取时使用的回调token，多次回调只保留最新的一个
+    pending: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Schedule {
+    accounts: Mutex<HashMap<String, Account>>,
+}
+
+impl Schedule {
+    /// 登记一次拉取，返回是否需要启动新的拉取任务
+    fn submit(&self, open_kfid: &str, token: &str) -> bool {
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(open_kfid.to_string()).or_default();
+        account.pending = Some(token.to_string());
+        !std::mem::replace(&mut account.running, true)
+    }
+
+    /// 取出下一次拉取使用的token，没有等待中的拉取时结束任务
+    fn next(&self, open_kfid: &str) -> Option<String> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.get_mut(open_kfid)?;
+        let token = account.pending.take();
+        if token.is_none() {
+            accounts.remove(open_kfid);
+        }
+        token
+    }
+
+    /// 拉取失败后放回token以便重试，期间收到了新的回调时使用新的token
+    fn retry(&self, open_kfid: &str, token: String) {
+        let mut accounts = self.accounts.lock().unwrap();
+        if let Some(account) = accounts.get_mut(open_kfid) {
+            account.pending.get_or_insert(token);
+        }
+    }
+
+    /// 清除客服账号的拉取状态，之后的回调会启动新的拉取任务
+    fn reset(&self, open_kfid: &str) {
+        if let Ok(mut accounts) = self.accounts.lock() {
+            accounts.remove(open_kfid);
+        }
+    }
+}
+
+/// 拉取任务因panic或被取消而中止时清除拉取状态，避免该客服账号再也不会被拉取
+struct RunGuard<'a> {
+    schedule: &'a Schedule,
+    open_kfid: &'a str,
+    /// 任务正常结束时拉取状态已由[`Schedule::next`]清除
+    finished: bool,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.schedule.reset(self.open_kfid);
+        }
+    }
+}
+
+/// 回调驱动的消息拉取
+///
+/// 每次收到`kf_msg_or_event`回调时，使用回调中的token为对应客服账号拉取消息。
+/// 同一客服账号的拉取串行执行以保证游标顺序，拉取进行中收到的多次回调合并为一次拉取。
+/// 拉取失败时保留回调token，按1、2、4、8秒退避后重试，同一个token最多尝试5次。
+/// 需要在tokio运行时中使用。
+#[derive(Clone)]
+pub struct SyncOrchestrator {
+    client: Client,
+    store: Arc<dyn CursorStore>,
+    dispatcher: Arc<Dispatcher>,
+    schedule: Arc<Schedule>,
+    on_error: Option<ErrorHandler>,
+}
+
+impl SyncOrchestrator {
+    pub fn new(client: Client, store: Arc<dyn CursorStore>, dispatcher: Arc<Dispatcher>) -> Self {
+        Self {
+            client,
+            store,
+            dispatcher,
+            schedule: Arc::default(),
+            on_error: None,
+        }
+    }
+
+    /// 设置拉取失败时的处理函数，参数为客服账号ID和错误
+    pub fn on_error<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, ClientErr) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(f));
+        self
+    }
+
+    /// 为客服账号安排一次拉取
+    pub fn notify(&self, open_kfid: &str, token: &str) {
+        if self.schedule.submit(open_kfid, token) {
+            let this = self.clone();
+            let open_kfid = open_kfid.to_string();
+            tokio::spawn(async move { this.run(&open_kfid).await });
+        }
+    }
+
+    async fn run(&self, open_kfid: &str) {
+        let mut guard = RunGuard {
+            schedule: &self.schedule,
+            open_kfid,
+            finished: false,
+        };
+        let mut failures = 0;
+        while let Some(token) = self.schedule.next(open_kfid) {
+            let result = pull(
+                &self.client,
+                self.store.as_ref(),
+                &self.dispatcher,
+                open_kfid,
+                Some(&token),
+            )
+            .await;
+            let Err(err) = result else {
+                failures = 0;
+                continue;
+            };
+            failures += 1;
+            #[cfg(feature = "tracing")]
+            tracing::warn!(open_kfid, error = ?err, attempt = failures, "sync failed");
+            if let Some(on_error) = &self.on_error {
+                on_error(open_kfid, err);
+            }
+            if failures < SYNC_ATTEMPTS {
+                self.schedule.retry(open_kfid, token);
+                tokio::time::sleep(SYNC_RETRY_DELAY * 2u32.pow(failures - 1)).await;
+            } else {
+                failures = 0;
+            }
+        }
+        guard.finished = true;
+    }
+}
+
+impl EventSink for SyncOrchestrator {
+    fn push(&self, event: WeiXinCallbackRes) -> bool {
+        if event.event == KF_MSG_OR_EVENT {
+            self.notify(&event.open_kf_id, &event.token);
+        }
+        true
+    }
+}
+
+impl std::fmt::Debug for SyncOrchestrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncOrchestrator")
+            .field("client", &self.client)
+            .field("dispatcher", &self.dispatcher)
+            .finish()
+    }
+}
+
+/// 轮询拉取的最短间隔
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// 轮询拉取的最长间隔
+pub const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 计算下一次轮询的间隔：还有更多消息时立即继续拉取，有新消息时恢复到最短间隔，
+/// 没有新消息时逐步加倍直至最长间隔
+fn next_interval(
+    current: Duration,
+    count: usize,
+    has_more: MoreMsg,
+    min: Duration,
+    max: Duration,
+) -> Duration {
+    if has_more == MoreMsg::Yes {
+        Duration::ZERO
+    } else if count > 0 {
+        min
+    } else {
+        (current * 2).clamp(min, max)
+    }
+}
+
+/// 轮询模式的消息拉取，用于无法接收回调的环境
+///
+/// 定期为每个客服账号调用`sync_msg`（不带回调token），与回调模式共用游标存储和分发器。
+/// 每次轮询拉取一页消息，`has_more`为`Yes`时立即拉取下一页，连续没有新消息时逐步延长间隔。
+#[derive(Clone)]
+pub struct PollingRunner {
+    client: Client,
+    store: Arc<dyn CursorStore>,
+    dispatcher: Arc<Dispatcher>,
+    open_kfids: Vec<String>,
+    min_interval: Duration,
+    max_interval: Duration,
+    on_error: Option<ErrorHandler>,
+}
+
+impl PollingRunner {
+    pub fn new(
+        client: Client,
+        store: Arc<dyn CursorStore>,
+        dispatcher: Arc<Dispatcher>,
+        open_kfids: Vec<String>,
+    ) -> Self {
+        Self {
+            client,
+            store,
+            dispatcher,
+            open_kfids,
+            min_interval: MIN_POLL_INTERVAL,
+            max_interval: MAX_POLL_INTERVAL,
+            on_error: None,
+        }
+    }
+
+    /// 设置轮询间隔的范围
+    pub fn interval(mut self, min: Duration, max: Duration) -> Self {
+        self.min_interval = min;
+        self.max_interval = max.max(min);
+        self
+    }
+
+    /// 设置拉取失败时的处理函数，参数为客服账号ID和错误
+    pub fn on_error<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, ClientErr) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(f));
+        self
+    }
+
+    /// 为每个客服账号启动轮询任务，需要在tokio运行时中调用
+    pub fn spawn(self) -> Vec<JoinHandle<()>> {
+        self.open_kfids
+            .iter()
+            .map(|open_kfid| {
+                let this = self.clone();
+                let open_kfid = open_kfid.clone();
+                tokio::spawn(async move { this.run(&open_kfid).await })
+            })
+            .collect()
+    }
+
+    async fn run(&self, open_kfid: &str) {
+        let mut interval = self.min_interval;
+        loop {
+            let result = pull_page(
+                &self.client,
+                self.store.as_ref(),
+                &self.dispatcher,
+                open_kfid,
+                None,
+            )
+            .await;
+            let (count, has_more) = match result {
+                Ok(page) => page,
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(open_kfid, error = ?err, "polling failed");
+                    if let Some(on_error) = &self.on_error {
+                        on_error(open_kfid, err);
+                    }
+                    (0, MoreMsg::No)
+                }
+            };
+            interval = next_interval(
+                interval,
+                count,
+                has_more,
+                self.min_interval,
+                self.max_interval,
+            );
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+impl std::fmt::Debug for PollingRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollingRunner")
+            .field("client", &self.client)
+            .field("open_kfids", &self.open_kfids)
+            .field("min_interval", &self.min_interval)
+            .field("max_interval", &self.max_interval)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher::{Context, Flow};
+    use crate::retry::RetryPolicy;
+    use crate::transport::mock::{mock_seq, MockTransport};
+    use tokio::sync::mpsc;
+
+    type Receiver = mpsc::UnboundedReceiver<String>;
+
+    fn sync_res(cursor: &str, has_more: u8, contents: &[&str]) -> String {
+        let msg_list: Vec<_> = contents
+            .iter()
+            .map(|content| {
+                serde_json::json!({
+                    "msgid": format!("MSG_{content}"),
+                    "open_kfid": "OPEN_KFID",
+                    "external_userid": "EXTERNAL_USERID",
+                    "send_time": 1615478585,
+                    "origin": 3,
+                    "msgtype": "text",
+                    "text": { "content": content }
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "errcode": 0,
+            "errmsg": "ok",
+            "next_cursor": cursor,
+            "has_more": has_more,
+            "msg_list": msg_list
+        })
+        .to_string()
+    }
+
+    /// 把收到的文本消息发送到通道，内容为`panic`时panic
+    fn forward(sender: mpsc::UnboundedSender<String>) -> Dispatcher {
+        Dispatcher::new().on_text(move |ctx: Context| {
+            let sender = sender.clone();
+            async move {
+                let content = ctx.item.message.text_content().unwrap().to_string();
+                if content == "panic" {
+                    panic!("handler panicked");
+                }
+                sender.send(content).unwrap();
+                Flow::Stop
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pull() {
+        let transport = mock_seq(&[
+            &sync_res("CURSOR_1", 1, &["1", "2"]),
+            &sync_res("CURSOR_2", 0, &["3"]),
+        ]);
+        let client = Client::with_transport("TOKEN", transport.clone());
+        let store = MemoryCursorStore::default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let count = pull(&client, &store, &forward(sender), "OPEN_KFID", Some("SYNC"))
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(store.get("OPEN_KFID").as_deref(), Some("CURSOR_2"));
+        for expected in ["1", "2", "3"] {
+            assert_eq!(receiver.recv().await.unwrap(), expected);
+        }
+
+        let requests = transport.requests.lock().unwrap();
+        let bodies: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|req| serde_json::from_slice(&req.body).unwrap())
+            .collect();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0]["token"], "SYNC");
+        assert!(bodies[0]["cursor"].is_null());
+        assert_eq!(bodies[1]["cursor"], "CURSOR_1");
+    }
+
+    fn stub_orchestrator(responses: &[&str]) -> (SyncOrchestrator, Arc<MockTransport>, Receiver) {
+        let transport = mock_seq(responses);
+        let client = Client::with_transport("TOKEN", transport.clone()).retry(RetryPolicy::none());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let orchestrator = SyncOrchestrator::new(
+            client,
+            Arc::new(MemoryCursorStore::default()),
+            Arc::new(forward(sender)),
+        );
+        (orchestrator, transport, receiver)
+    }
+
+    fn is_running(orchestrator: &SyncOrchestrator) -> bool {
+        let accounts = orchestrator.schedule.accounts.lock().unwrap();
+        accounts.contains_key("OPEN_KFID")
+    }
+
+    fn request_bodies(transport: &MockTransport) -> Vec<serde_json::Value> {
+        let requests = transport.requests.lock().unwrap();
+        requests
+            .iter()
+            .map(|req| serde_json::from_slice(&req.body).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_orchestrator_handler_panic() {
+        let (orchestrator, transport, mut receiver) = stub_orchestrator(&[
+            &sync_res("CURSOR_1", 0, &["panic", "after"]),
+            &sync_res("CURSOR_2", 0, &["ok"]),
+        ]);
+        orchestrator.notify("OPEN_KFID", "SYNC_1");
+        assert_eq!(receiver.recv().await.unwrap(), "after");
+        while is_running(&orchestrator) {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            orchestrator.store.get("OPEN_KFID").as_deref(),
+            Some("CURSOR_1")
+        );
+
+        orchestrator.notify("OPEN_KFID", "SYNC_2");
+        assert_eq!(receiver.recv().await.unwrap(), "ok");
+        let bodies = request_bodies(&transport);
+        assert_eq!(bodies[1]["cursor"], "CURSOR_1");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_orchestrator_retry() {
+        let (orchestrator, transport, mut receiver) = stub_orchestrator(&[
+            r#"{"errcode":-1,"errmsg":"system busy"}"#,
+            r#"{"errcode":-1,"errmsg":"system busy"}"#,
+            &sync_res("CURSOR_1", 0, &["1"]),
+        ]);
+        orchestrator.notify("OPEN_KFID", "SYNC");
+        assert_eq!(receiver.recv().await.unwrap(), "1");
+        let bodies = request_bodies(&transport);
+        assert_eq!(bodies.len(), 3);
+        assert!(bodies.iter().all(|body| body["token"] == "SYNC"));
+
+        let (orchestrator, transport, _receiver) =
+            stub_orchestrator(&[r#"{"errcode":-1,"errmsg":"system busy"}"#]);
+        orchestrator.notify("OPEN_KFID", "SYNC");
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(request_bodies(&transport).len(), SYNC_ATTEMPTS as usize);
+        assert!(!is_running(&orchestrator));
+    }
+
+    #[tokio::test]
+    async fn test_orchestrator_cancelled() {
+        let transport = mock_seq(&[&sync_res("CURSOR_1", 0, &["1"])]);
+        let client = Client::with_transport("TOKEN", transport);
+        let dispatcher = Dispatcher::new().on_text(|_| std::future::pending());
+        let orchestrator = SyncOrchestrator::new(
+            client,
+            Arc::new(MemoryCursorStore::default()),
+            Arc::new(dispatcher),
+        );
+        assert!(orchestrator.schedule.submit("OPEN_KFID", "SYNC"));
+        let run = orchestrator.run("OPEN_KFID");
+        assert!(tokio::time::timeout(Duration::from_millis(10), run)
+            .await
+            .is_err());
+        assert!(!is_running(&orchestrator));
+        assert!(orchestrator.schedule.submit("OPEN_KFID", "SYNC"));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let schedule = Schedule::default();
+        assert!(schedule.submit("A", "1"));
+        assert!(!schedule.submit("A", "2"));
+        assert!(!schedule.submit("A", "3"));
+        assert!(schedule.submit("B", "1"));
+
+        assert_eq!(schedule.next("A"), Some("3".to_string()));
+        assert!(!schedule.submit("A", "4"));
+        assert_eq!(schedule.next("A"), Some("4".to_string()));
+        assert_eq!(schedule.next("A"), None);
+        assert!(schedule.submit("A", "5"));
+
+        assert_eq!(schedule.next("A"), Some("5".to_string()));
+        schedule.retry("A", "5".to_string());
+        assert_eq!(schedule.next("A"), Some("5".to_string()));
+        assert!(!schedule.submit("A", "6"));
+        schedule.retry("A", "5".to_string());
+        assert_eq!(schedule.next("A"), Some("6".to_string()));
+    }
+
+    #[test]
+    fn test_memory_cursor_store() {
+        let store = MemoryCursorStore::default();
+        assert_eq!(store.get("A"), None);
+        store.set("A", "CURSOR");
+        assert_eq!(store.get("A"), Some("CURSOR".to_string()));
+    }
+
+    #[test]
+    fn test_next_interval() {
+        let min = Duration::from_secs(1);
+        let max = Duration::from_secs(5);
+        let interval = next_interval(min, 0, MoreMsg::No, min, max);
+        assert_eq!(interval, Duration::from_secs(2));
+        let interval = next_interval(interval, 0, MoreMsg::No, min, max);
+        assert_eq!(interval, Duration::from_secs(4));
+        let interval = next_interval(interval, 0, MoreMsg::No, min, max);
+        assert_eq!(interval, max);
+        assert_eq!(next_interval(interval, 3, MoreMsg::No, min, max), min);
+        let interval = next_interval(interval, 3, MoreMsg::Yes, min, max);
+        assert_eq!(interval, Duration::ZERO);
+        assert_eq!(next_interval(interval, 0, MoreMsg::No, min, max), min);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_polling_backoff() {
+        let transport = mock_seq(&[
+            &sync_res("CURSOR_1", 1, &["1"]),
+            &sync_res("CURSOR_2", 0, &["2"]),
+            &sync_res("CURSOR_2", 0, &[]),
+        ]);
+        let client = Client::with_transport("TOKEN", transport.clone());
+        let store = Arc::new(MemoryCursorStore::default());
+        let runner = PollingRunner::new(
+            client,
+            store.clone(),
+            Arc::new(Dispatcher::new()),
+            vec!["OPEN_KFID".to_string()],
+        )
+        .interval(Duration::from_secs(1), Duration::from_secs(8));
+        let handles = runner.spawn();
+        let requests = || transport.requests.lock().unwrap().len();
+
+        // 第一页has_more为Yes，立即拉取第二页；之后在第1、3、7、15、23秒轮询，间隔逐步加倍直至8秒
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(requests(), 2);
+        assert_eq!(store.get("OPEN_KFID").as_deref(), Some("CURSOR_2"));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(requests(), 3);
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(requests(), 5);
+        tokio::time::sleep(Duration::from_secs(8)).await;
+        assert_eq!(requests(), 6);
+        tokio::time::sleep(Duration::from_secs(15)).await;
+        assert_eq!(requests(), 7);
+        handles.iter().for_each(JoinHandle::abort);
+    }
+}
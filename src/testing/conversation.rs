@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use super::MockServer;
+use crate::client::{Client, ClientErr};
+use crate::dispatcher::Dispatcher;
+use crate::retry::RetryPolicy;
+use crate::service_state::ServiceState;
+use crate::sync::{self, MemoryCursorStore};
+
+/// 机器人发给客户的一条消息，即模拟服务记录的请求体
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply(pub Value);
+
+impl Reply {
+    /// 消息类型，如`text`、`msgmenu`
+    pub fn msgtype(&self) -> &str {
+        self.0["msgtype"].as_str().unwrap_or_default()
+    }
+
+    /// 文本消息的内容，消息不是文本消息时返回`None`
+    pub fn text(&self) -> Option<&str> {
+        self.0["text"]["content"].as_str()
+    }
+
+    /// 菜单消息中回复菜单的ID
+    pub fn menu_ids(&self) -> Vec<&str> {
+        self.0["msgmenu"]["list"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item["click"]["id"].as_str())
+            .collect()
+    }
+
+    /// 是否为使用事件code发送的事件响应消息，如欢迎语、回复语和结束语
+    pub fn is_event_response(&self) -> bool {
+        self.0.get("code").is_some()
+    }
+}
+
+/// 模拟一位微信客户与机器人的会话
+///
+/// 每个动作都会先在[`MockServer`]中生成客户的消息或事件，再像真实部署一样通过`sync_msg`
+/// 拉取并交给分发器处理，最后返回处理期间机器人发给该客户的消息。
+///
+/// ```ignore
+/// let server = MockServer::default();
+/// let mut conversation = Conversation::new(&server, bot());
+/// let replies = conversation.enter_session("SCENE").await?;
+/// assert!(replies[0].menu_ids().contains(&"human"));
+/// let replies = conversation.click_menu("human", "转人工").await?;
+/// assert_eq!(conversation.service_state(), ServiceState::Pool);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    server: MockServer,
+    client: Client,
+    dispatcher: Arc<Dispatcher>,
+    cursors: Arc<MemoryCursorStore>,
+    open_kfid: String,
+    external_userid: String,
+    /// 已返回过的消息条数
+    seen: usize,
+}
+
+impl Conversation {
+    /// 在模拟服务中创建客服账号，由`dispatcher`处理客户`EXTERNAL_USERID`的消息
+    pub fn new(server: &MockServer, dispatcher: Dispatcher) -> Self {
+        let open_kfid = server.add_account("客服");
+        let client = Client::with_transport(&server.issue_token(), server.clone())
+            .retry(RetryPolicy::none());
+        Self {
+            server: server.clone(),
+            client,
+            dispatcher: Arc::new(dispatcher),
+            cursors: Arc::default(),
+            open_kfid,
+            external_userid: "EXTERNAL_USERID".to_string(),
+            seen: 0,
+        }
+    }
+
+    /// 同一客服账号下另一位客户的会话，与当前会话共用分发器和拉取游标
+    pub fn customer(&self, external_userid: &str) -> Self {
+        Self {
+            external_userid: external_userid.to_string(),
+            seen: self.server.sent_to(external_userid).len(),
+            ..self.clone()
+        }
+    }
+
+    /// 处理器使用的客户端
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn open_kfid(&self) -> &str {
+        &self.open_kfid
+    }
+
+    pub fn external_userid(&self) -> &str {
+        &self.external_userid
+    }
+
+    /// 客户从场景`scene`进入会话
+    pub async fn enter_session(&mut self, scene: &str) -> Result<Vec<Reply>, ClientErr> {
+        self.server
+            .enter_session(&self.open_kfid, &self.external_userid, scene);
+        self.deliver().await
+    }
+
+    /// 客户发送文本消息
+    pub async fn send_text(&mut self, content: &str) -> Result<Vec<Reply>, ClientErr> {
+        self.server
+            .push_text(&self.open_kfid, &self.external_userid, content);
+        self.deliver().await
+    }
+
+    /// 客户点击回复菜单，`content`为菜单项的文本
+    pub async fn click_menu(
+        &mut self,
+        menu_id: &str,
+        content: &str,
+    ) -> Result<Vec<Reply>, ClientErr> {
+        self.server
+            .click_menu(&self.open_kfid, &self.external_userid, menu_id, content);
+        self.deliver().await
+    }
+
+    /// 客户当前的会话状态
+    pub fn service_state(&self) -> ServiceState {
+        self.server
+            .service_state(&self.open_kfid, &self.external_userid)
+            .0
+    }
+
+    /// 人工接待时的接待人员
+    pub fn servicer(&self) -> Option<String> {
+        self.server
+            .service_state(&self.open_kfid, &self.external_userid)
+            .1
+    }
+
+    /// 拉取并分发新消息，返回新发给客户的消息
+    async fn deliver(&mut self) -> Result<Vec<Reply>, ClientErr> {
+        let cursors = self.cursors.as_ref();
+        sync::pull(
+            &self.client,
+            cursors,
+            &self.dispatcher,
+            &self.open_kfid,
+            None,
+        )
+        .await?;
+        let sent = self.server.sent_to(&self.external_userid);
+        let replies = sent[self.seen..].iter().cloned().map(Reply).collect();
+        self.seen = sent.len();
+        Ok(replies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher::{Context, Flow};
+    use crate::msg_res::{KfEvent, Message};
+    use crate::send::MenuBuilder;
+    use crate::service_state::TransServiceState;
+    use crate::welcome::{EventCode, Welcome};
+
+    /// 进入会话时发送菜单欢迎语，VIP场景直接转人工；点击“转人工”后进入排队
+    fn bot() -> Dispatcher {
+        Dispatcher::new()
+            .on_event(KfEvent::EnterSession, |ctx: Context| async move {
+                let Message::Event {
+                    open_kfid,
+                    external_userid,
+                    scene,
+                    ..
+                } = &ctx.item.message
+                else {
+                    return Flow::Next;
+                };
+                let code = EventCode::from_item(&ctx.item).unwrap().code;
+                if scene.as_deref() == Some("vip") {
+                    let req =
+                        TransServiceState::new(open_kfid, external_userid, ServiceState::Servicer)
+                            .servicer("SERVICER_USERID");
+                    ctx.client.call(&req).await.unwrap();
+                    let welcome = Welcome::text(&code, "正在为您转接专属客服");
+                    ctx.client.send_welcome(&welcome).await.unwrap();
+                } else {
+                    let menu = MenuBuilder::default()
+                        .head("您好，请选择")
+                        .click("faq", "常见问题")
+                        .click("human", "转人工")
+                        .into_menu();
+                    ctx.client
+                        .send_welcome(&Welcome::menu(&code, menu))
+                        .await
+                        .unwrap();
+                }
+                Flow::Stop
+            })
+            .on(
+                |item| {
+                    matches!(&item.message, Message::Text { menu_id: Some(id), .. } if id == "human")
+                },
+                |ctx: Context| async move {
+                    let open_kfid = ctx.item.open_kfid.as_deref().unwrap();
+                    let external_userid = ctx.item.external_userid.as_deref().unwrap();
+                    let req =
+                        TransServiceState::new(open_kfid, external_userid, ServiceState::Pool);
+                    let res = ctx.client.call(&req).await.unwrap();
+                    let welcome = Welcome::text(&res.msg_code.unwrap(), "正在为您排队");
+                    ctx.client.send_welcome(&welcome).await.unwrap();
+                    Flow::Stop
+                },
+            )
+            .on_text(|ctx: Context| async move {
+                let content = ctx.item.message.text_content().unwrap_or_default();
+                ctx.reply_text(&format!("收到：{content}")).await.unwrap();
+                Flow::Stop
+            })
+    }
+
+    #[tokio::test]
+    async fn test_conversation() {
+        let server = MockServer::default();
+        let mut conversation = Conversation::new(&server, bot());
+
+        let replies = conversation.enter_session("SCENE").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert!(replies[0].is_event_response());
+        assert_eq!(replies[0].msgtype(), "msgmenu");
+        assert_eq!(replies[0].menu_ids(), ["faq", "human"]);
+        assert_eq!(conversation.service_state(), ServiceState::Untreated);
+
+        let replies = conversation.send_text("你好").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].text(), Some("收到：你好"));
+        assert_eq!(replies[0].0["touser"], "EXTERNAL_USERID");
+
+        // 点击菜单时文本消息带有menu_id，由菜单处理器而不是文本处理器处理
+        let replies = conversation.click_menu("human", "转人工").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].text(), Some("正在为您排队"));
+        assert!(replies[0].is_event_response());
+        assert_eq!(conversation.service_state(), ServiceState::Pool);
+
+        let replies = conversation.click_menu("faq", "常见问题").await.unwrap();
+        assert_eq!(replies[0].text(), Some("收到：常见问题"));
+    }
+
+    #[tokio::test]
+    async fn test_customers() {
+        let server = MockServer::default();
+        let mut alice = Conversation::new(&server, bot());
+        let mut bob = alice.customer("BOB");
+
+        alice.enter_session("SCENE").await.unwrap();
+        let replies = bob.enter_session("vip").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].text(), Some("正在为您转接专属客服"));
+        assert_eq!(bob.service_state(), ServiceState::Servicer);
+        assert_eq!(bob.servicer().as_deref(), Some("SERVICER_USERID"));
+        assert_eq!(alice.service_state(), ServiceState::Untreated);
+
+        let replies = alice.send_text("在吗").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].0["touser"], alice.external_userid());
+        let replies = bob.send_text("hi").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].0["touser"], "BOB");
+    }
+}
@@ -0,0 +1,866 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ::axum::body::Bytes;
+use ::axum::extract::State;
+use ::axum::http::Uri;
+use ::axum::{Json, Router};
+use serde_json::{json, Value};
+
+use crate::callback::CallbackConfig;
+use crate::fixtures::{error, ok, ItemBuilder};
+use crate::msg_res::KfEvent;
+use crate::parse::WeiXinCallbackRes;
+use crate::service_state::ServiceState;
+use crate::transport::{
+    BoxFuture, HttpRequest, HttpResponse, HttpTransport, Method, ReqwestTransport, TransportErr,
+};
+
+mod conversation;
+
+pub use crate::fixtures::{
+    DUPLICATE_MSGID, INVALID_MSG_TOKEN, INVALID_PARAMETER, INVALID_SECRET, INVALID_TOKEN,
+};
+pub use conversation::{Conversation, Reply};
+
+/// 每次拉取的默认条数
+const DEFAULT_SYNC_LIMIT: usize = 1000;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn str_field<'a>(body: &'a Value, key: &str) -> Option<&'a str> {
+    body.get(key).and_then(Value::as_str)
+}
+
+#[derive(Debug)]
+struct Account {
+    open_kfid: String,
+    name: String,
+    media_id: String,
+}
+
+#[derive(Debug)]
+struct Code {
+    external_userid: String,
+    used: bool,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    corpid: String,
+    secret: String,
+    counter: u64,
+    tokens: HashSet<String>,
+    /// 回调中签发的拉取消息token及其所属的客服账号
+    sync_tokens: HashMap<String, String>,
+    accounts: Vec<Account>,
+    servicers: HashMap<String, Vec<String>>,
+    /// 每个客服账号待拉取的消息，游标为消息的下标
+    messages: HashMap<String, Vec<Value>>,
+    sent: Vec<Value>,
+    recalled: Vec<String>,
+    /// 事件响应消息的code
+    codes: HashMap<String, Code>,
+    /// 每个（客服账号，客户）的会话状态和接待人员
+    sessions: HashMap<(String, String), (ServiceState, Option<String>)>,
+    errors: HashMap<String, VecDeque<i32>>,
+    latency: Duration,
+}
+
+impl MockState {
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.counter += 1;
+        format!("{prefix}{}", self.counter)
+    }
+
+    fn get_token(&mut self, query: &HashMap<String, String>) -> Value {
+        let corpid = query.get("corpid").map(String::as_str);
+        let secret = query.get("corpsecret").map(String::as_str);
+        if corpid != Some(self.corpid.as_str()) || secret != Some(self.secret.as_str()) {
+            return error(INVALID_SECRET);
+        }
+        let token = self.next_id("MOCK_ACCESS_TOKEN_");
+        self.tokens.insert(token.clone());
+        ok(json!({ "access_token": token, "expires_in": 7200 }))
+    }
+
+    fn account_index(&self, body: &Value) -> Option<usize> {
+        let open_kfid = str_field(body, "open_kfid")?;
+        self.accounts
+            .iter()
+            .position(|account| account.open_kfid == open_kfid)
+    }
+
+    fn add_account(&mut self, body: &Value) -> Value {
+        let Some(name) = str_field(body, "name") else {
+            return error(INVALID_PARAMETER);
+        };
+        let account = Account {
+            open_kfid: self.next_id("wkMOCK"),
+            name: name.to_string(),
+            media_id: str_field(body, "media_id").unwrap_or_default().to_string(),
+        };
+        let res = ok(json!({ "open_kfid": account.open_kfid }));
+        self.accounts.push(account);
+        res
+    }
+
+    fn del_account(&mut self, body: &Value) -> Value {
+        match self.account_index(body) {
+            Some(index) => {
+                self.accounts.remove(index);
+                ok(json!({}))
+            }
+            None => error(INVALID_PARAMETER),
+        }
+    }
+
+    fn update_account(&mut self, body: &Value) -> Value {
+        let Some(index) = self.account_index(body) else {
+            return error(INVALID_PARAMETER);
+        };
+        let account = &mut self.accounts[index];
+        if let Some(name) = str_field(body, "name") {
+            account.name = name.to_string();
+        }
+        if let Some(media_id) = str_field(body, "media_id") {
+            account.media_id = media_id.to_string();
+        }
+        ok(json!({}))
+    }
+
+    fn list_accounts(&self, body: &Value) -> Value {
+        let offset = body.get("offset").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let limit = body.get("limit").and_then(Value::as_u64).unwrap_or(100) as usize;
+        let account_list: Vec<Value> = self
+            .accounts
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|account| {
+                json!({
+                    "open_kfid": account.open_kfid,
+                    "name": account.name,
+                    "avatar": format!("https://mock/avatar/{}", account.media_id),
+                })
+            })
+            .collect();
+        ok(json!({ "account_list": account_list }))
+    }
+
+    fn add_contact_way(&self, body: &Value) -> Value {
+        match self.account_index(body) {
+            Some(index) => {
+                let open_kfid = &self.accounts[index].open_kfid;
+                ok(json!({ "url": format!("https://work.weixin.qq.com/kfid/{open_kfid}") }))
+            }
+            None => error(INVALID_PARAMETER),
+        }
+    }
+
+    fn update_servicers(&mut self, body: &Value, add: bool) -> Value {
+        let (Some(open_kfid), Some(userids)) = (
+            str_field(body, "open_kfid"),
+            body.get("userid_list").and_then(Value::as_array),
+        ) else {
+            return error(INVALID_PARAMETER);
+        };
+        let servicers = self.servicers.entry(open_kfid.to_string()).or_default();
+        let mut result_list = vec![];
+        for userid in userids.iter().filter_map(Value::as_str) {
+            let exists = servicers.iter().any(|servicer| servicer == userid);
+            if add && !exists {
+                servicers.push(userid.to_string());
+            } else if !add {
+                servicers.retain(|servicer| servicer != userid);
+            }
+            result_list.push(json!({ "userid": userid, "errcode": 0, "errmsg": "ok" }));
+        }
+        ok(json!({ "result_list": result_list }))
+    }
+
+    fn list_servicers(&self, query: &HashMap<String, String>) -> Value {
+        let Some(open_kfid) = query.get("open_kfid") else {
+            return error(INVALID_PARAMETER);
+        };
+        let servicer_list: Vec<Value> = self
+            .servicers
+            .get(open_kfid)
+            .into_iter()
+            .flatten()
+            .map(|userid| json!({ "userid": userid, "status": 0 }))
+            .collect();
+        ok(json!({ "servicer_list": servicer_list }))
+    }
+
+    fn sync_msg(&self, body: &Value) -> Value {
+        let Some(open_kfid) = str_field(body, "open_kfid") else {
+            return error(INVALID_PARAMETER);
+        };
+        if let Some(token) = str_field(body, "token") {
+            if self.sync_tokens.get(token).map(String::as_str) != Some(open_kfid) {
+                return error(INVALID_MSG_TOKEN);
+            }
+        }
+        let messages = self
+            .messages
+            .get(open_kfid)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let start = match str_field(body, "cursor") {
+            Some(cursor) => match cursor.parse::<usize>() {
+                Ok(start) if start <= messages.len() => start,
+                _ => return error(INVALID_PARAMETER),
+            },
+            None => 0,
+        };
+        let limit = body
+            .get("limit")
+            .and_then(Value::as_u64)
+            .map_or(DEFAULT_SYNC_LIMIT, |limit| limit as usize)
+            .clamp(1, DEFAULT_SYNC_LIMIT);
+        let end = (start + limit).min(messages.len());
+        ok(json!({
+            "next_cursor": end.to_string(),
+            "has_more": u8::from(end < messages.len()),
+            "msg_list": messages[start..end],
+        }))
+    }
+
+    fn is_sent(&self, msgid: &str) -> bool {
+        self.sent
+            .iter()
+            .any(|message| str_field(message, "msgid") == Some(msgid))
+    }
+
+    /// 记录发送的消息，返回消息ID
+    fn record_sent(&mut self, body: &Value) -> Result<String, Value> {
+        let msgid = match str_field(body, "msgid") {
+            Some(msgid) if self.is_sent(msgid) => return Err(error(DUPLICATE_MSGID)),
+            Some(msgid) => msgid.to_string(),
+            None => self.next_id("MOCK_MSGID_"),
+        };
+        let mut message = body.clone();
+        message["msgid"] = json!(msgid);
+        message["send_time"] = json!(now());
+        self.sent.push(message);
+        Ok(msgid)
+    }
+
+    fn send_msg(&mut self, body: &Value) -> Value {
+        if str_field(body, "touser").is_none() || str_field(body, "open_kfid").is_none() {
+            return error(INVALID_PARAMETER);
+        }
+        match self.record_sent(body) {
+            Ok(msgid) => ok(json!({ "msgid": msgid })),
+            Err(res) => res,
+        }
+    }
+
+    fn send_msg_on_event(&mut self, body: &Value) -> Value {
+        let Some(code) = str_field(body, "code") else {
+            return error(INVALID_PARAMETER);
+        };
+        if !matches!(self.codes.get(code), Some(code) if !code.used) {
+            return error(INVALID_PARAMETER);
+        }
+        match self.record_sent(body) {
+            Ok(msgid) => {
+                if let Some(code) = self.codes.get_mut(code) {
+                    code.used = true;
+                }
+                ok(json!({ "msgid": msgid }))
+            }
+            Err(res) => res,
+        }
+    }
+
+    fn new_code(&mut self, prefix: &str, external_userid: &str) -> String {
+        let code = self.next_id(prefix);
+        let value = Code {
+            external_userid: external_userid.to_string(),
+            used: false,
+        };
+        self.codes.insert(code.clone(), value);
+        code
+    }
+
+    fn session_key(body: &Value) -> Option<(String, String)> {
+        let open_kfid = str_field(body, "open_kfid")?;
+        let external_userid = str_field(body, "external_userid")?;
+        Some((open_kfid.to_string(), external_userid.to_string()))
+    }
+
+    fn get_service_state(&self, body: &Value) -> Value {
+        let Some(key) = Self::session_key(body) else {
+            return error(INVALID_PARAMETER);
+        };
+        let (state, servicer) = self.sessions.get(&key).cloned().unwrap_or_default();
+        let mut res = ok(json!({ "service_state": state }));
+        if let Some(servicer) = servicer {
+            res["servicer_userid"] = json!(servicer);
+        }
+        res
+    }
+
+    fn trans_service_state(&mut self, body: &Value) -> Value {
+        let (Some(key), Some(state)) = (
+            Self::session_key(body),
+            body.get("service_state")
+                .and_then(|state| serde_json::from_value::<ServiceState>(state.clone()).ok()),
+        ) else {
+            return error(INVALID_PARAMETER);
+        };
+        let servicer = str_field(body, "servicer_userid").map(str::to_string);
+        if state == ServiceState::Servicer && servicer.is_none() {
+            return error(INVALID_PARAMETER);
+        }
+        let previous = self.sessions.get(&key).map(|(state, _)| *state);
+        let external_userid = key.1.clone();
+        self.sessions.insert(key, (state, servicer));
+        // 首次进入排队、人工接待或结束状态时返回回复语或结束语的code
+        let replies = matches!(
+            state,
+            ServiceState::Pool | ServiceState::Servicer | ServiceState::Ended
+        );
+        if replies && previous != Some(state) {
+            let code = self.new_code("MOCK_MSG_CODE_", &external_userid);
+            ok(json!({ "msg_code": code }))
+        } else {
+            ok(json!({}))
+        }
+    }
+
+    fn recall_msg(&mut self, body: &Value) -> Value {
+        match str_field(body, "msgid") {
+            Some(msgid) if self.is_sent(msgid) && !self.recalled.iter().any(|m| m == msgid) => {
+                self.recalled.push(msgid.to_string());
+                ok(json!({}))
+            }
+            _ => error(INVALID_PARAMETER),
+        }
+    }
+
+    fn handle(
+        &mut self,
+        method: Method,
+        path: &str,
+        query: &HashMap<String, String>,
+        body: &Value,
+    ) -> Value {
+        if let Some(errcode) = self.errors.get_mut(path).and_then(VecDeque::pop_front) {
+            return error(errcode);
+        }
+        if (method, path) == (Method::Get, "/gettoken") {
+            return self.get_token(query);
+        }
+        let authorized = query
+            .get("access_token")
+            .is_some_and(|token| self.tokens.contains(token));
+        if !authorized {
+            return error(INVALID_TOKEN);
+        }
+        match (method, path) {
+            (Method::Post, "/kf/account/add") => self.add_account(body),
+            (Method::Post, "/kf/account/del") => self.del_account(body),
+            (Method::Post, "/kf/account/update") => self.update_account(body),
+            (Method::Post, "/kf/account/list") => self.list_accounts(body),
+            (Method::Post, "/kf/add_contact_way") => self.add_contact_way(body),
+            (Method::Post, "/kf/servicer/add") => self.update_servicers(body, true),
+            (Method::Post, "/kf/servicer/del") => self.update_servicers(body, false),
+            (Method::Get, "/kf/servicer/list") => self.list_servicers(query),
+            (Method::Post, "/kf/sync_msg") => self.sync_msg(body),
+            (Method::Post, "/kf/send_msg") => self.send_msg(body),
+            (Method::Post, "/kf/recall_msg") => self.recall_msg(body),
+            (Method::Post, "/kf/send_msg_on_event") => self.send_msg_on_event(body),
+            (Method::Post, "/kf/service_state/get") => self.get_service_state(body),
+            (Method::Post, "/kf/service_state/trans") => self.trans_service_state(body),
+            _ => error(INVALID_PARAMETER),
+        }
+    }
+}
+
+/// 拆分请求地址，返回去掉`/cgi-bin`前缀的路径和URL解码后的查询参数
+fn split_url(url: &str) -> (&str, HashMap<String, String>) {
+    let path = match url.find("://") {
+        Some(index) => {
+            let rest = &url[index + 3..];
+            rest.find('/').map_or("/", |index| &rest[index..])
+        }
+        None => url,
+    };
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let path = path.strip_prefix("/cgi-bin").unwrap_or(path);
+    let query = serde_urlencoded::from_str(query).unwrap_or_default();
+    (path, query)
+}
+
+/// 本地模拟的客服接口服务，状态保存在内存中
+///
+/// 既可以通过[`MockServer::start`]启动HTTP服务，将[`Client::base_url`](crate::Client::base_url)
+/// 设置为返回的地址；也可以直接作为[`HttpTransport`]传给客户端，不经过网络。
+#[derive(Debug, Clone)]
+pub struct MockServer {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new("CORP_ID", "SECRET")
+    }
+}
+
+impl MockServer {
+    /// 使用指定的企业ID和应用Secret创建服务
+    pub fn new(corpid: &str, secret: &str) -> Self {
+        let state = MockState {
+            corpid: corpid.to_string(),
+            secret: secret.to_string(),
+            ..Default::default()
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// 直接签发一个有效的access_token
+    pub fn issue_token(&self) -> String {
+        let mut state = self.state.lock().unwrap();
+        let token = state.next_id("MOCK_ACCESS_TOKEN_");
+        state.tokens.insert(token.clone());
+        token
+    }
+
+    /// 添加客服账号，返回open_kfid
+    pub fn add_account(&self, name: &str) -> String {
+        let res = self
+            .state
+            .lock()
+            .unwrap()
+            .add_account(&json!({ "name": name }));
+        res["open_kfid"].as_str().unwrap_or_default().to_string()
+    }
+
+    /// 添加一条待拉取的原始消息
+    pub fn push_message(&self, open_kfid: &str, message: Value) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .messages
+            .entry(open_kfid.to_string())
+            .or_default()
+            .push(message);
+    }
+
+    /// 模拟微信客户发送文本消息，返回消息ID
+    pub fn push_text(&self, open_kfid: &str, external_userid: &str, content: &str) -> String {
+        self.push_customer_text(open_kfid, external_userid, ItemBuilder::text(content))
+    }
+
+    /// 模拟微信客户点击菜单消息中的回复菜单，返回消息ID
+    pub fn click_menu(
+        &self,
+        open_kfid: &str,
+        external_userid: &str,
+        menu_id: &str,
+        content: &str,
+    ) -> String {
+        let text = ItemBuilder::menu_click(menu_id, content);
+        self.push_customer_text(open_kfid, external_userid, text)
+    }
+
+    fn push_customer_text(
+        &self,
+        open_kfid: &str,
+        external_userid: &str,
+        text: ItemBuilder,
+    ) -> String {
+        let msgid = self.state.lock().unwrap().next_id("MOCK_MSG_");
+        let item = text
+            .msgid(&msgid)
+            .open_kfid(open_kfid)
+            .external_userid(external_userid)
+            .send_time(now());
+        self.push_message(open_kfid, item.to_json());
+        msgid
+    }
+
+    /// 模拟微信客户从场景`scene`进入会话，返回可用于发送欢迎语的code
+    pub fn enter_session(&self, open_kfid: &str, external_userid: &str, scene: &str) -> String {
+        let (msgid, code) = {
+            let mut state = self.state.lock().unwrap();
+            let code = state.new_code("MOCK_WELCOME_CODE_", external_userid);
+            (state.next_id("MOCK_MSG_"), code)
+        };
+        let item = ItemBuilder::event(KfEvent::EnterSession)
+            .msgid(&msgid)
+            .open_kfid(open_kfid)
+            .external_userid(external_userid)
+            .send_time(now())
+            .field("scene", scene)
+            .field("scene_param", Value::Null)
+            .field("wechat_channels", Value::Null)
+            .field("welcome_code", code.as_str());
+        self.push_message(open_kfid, item.to_json());
+        code
+    }
+
+    /// 已发送的消息，包括事件响应消息
+    pub fn sent_messages(&self) -> Vec<Value> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// 发给客户的消息，包括使用该客户事件code发送的事件响应消息
+    pub fn sent_to(&self, external_userid: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .sent
+            .iter()
+            .filter(|message| match str_field(message, "code") {
+                Some(code) => state
+                    .codes
+                    .get(code)
+                    .is_some_and(|code| code.external_userid == external_userid),
+                None => str_field(message, "touser") == Some(external_userid),
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 客户当前的会话状态和接待人员
+    pub fn service_state(
+        &self,
+        open_kfid: &str,
+        external_userid: &str,
+    ) -> (ServiceState, Option<String>) {
+        let key = (open_kfid.to_string(), external_userid.to_string());
+        let state = self.state.lock().unwrap();
+        state.sessions.get(&key).cloned().unwrap_or_default()
+    }
+
+    /// 已撤回的消息ID
+    pub fn recalled(&self) -> Vec<String> {
+        self.state.lock().unwrap().recalled.clone()
+    }
+
+    /// 接口的下一次请求返回指定的错误码，多次调用时按顺序生效
+    pub fn fail_next(&self, path: &str, errcode: i32) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .errors
+            .entry(path.to_string())
+            .or_default()
+            .push_back(errcode);
+    }
+
+    /// 设置每次请求的响应延迟
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// 处理请求，`url`可以是完整地址或只包含路径和查询参数
+    pub async fn respond(&self, method: Method, url: &str, body: &[u8]) -> Value {
+        let latency = self.state.lock().unwrap().latency;
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        let (path, query) = split_url(url);
+        let body = serde_json::from_slice(body).unwrap_or(Value::Null);
+        self.state
+            .lock()
+            .unwrap()
+            .handle(method, path, &query, &body)
+    }
+
+    /// 在本地随机端口启动HTTP服务，返回的[`ServerGuard`]被drop时停止服务
+    pub async fn start(&self) -> std::io::Result<ServerGuard> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = Router::new().fallback(serve).with_state(self.clone());
+        let task = tokio::spawn(async move { ::axum::serve(listener, app).await });
+        Ok(ServerGuard {
+            url: format!("http://{addr}"),
+            task,
+        })
+    }
+
+    /// 生成通知客服账号有新消息的加密回调请求
+    pub fn callback_request(
+        &self,
+        url: &str,
+        config: &CallbackConfig,
+        open_kfid: &str,
+    ) -> Result<HttpRequest, TransportErr> {
+        let (corpid, token, nonce) = {
+            let mut state = self.state.lock().unwrap();
+            let token = state.next_id("MOCK_SYNC_TOKEN_");
+            state
+                .sync_tokens
+                .insert(token.clone(), open_kfid.to_string());
+            let nonce = state.next_id("");
+            (state.corpid.clone(), token, nonce)
+        };
+        let event = WeiXinCallbackRes::new(&corpid, &token, open_kfid);
+        let (body, params) = config
+            .encrypt(&corpid, &event.to_xml(), &now().to_string(), &nonce)
+            .map_err(|e| TransportErr::Other(format!("{e:?}")))?;
+        let sep = if url.contains('?') { '&' } else { '?' };
+        Ok(HttpRequest {
+            method: Method::Post,
+            url: format!(
+                "{url}{sep}msg_signature={}&timestamp={}&nonce={}",
+                params.msg_signature, params.timestamp, params.nonce
+            ),
+            headers: vec![("content-type".to_string(), "text/xml".to_string())],
+            body: body.into_bytes(),
+        })
+    }
+
+    /// 向回调地址发送通知客服账号有新消息的加密回调
+    pub async fn notify(
+        &self,
+        url: &str,
+        config: &CallbackConfig,
+        open_kfid: &str,
+    ) -> Result<HttpResponse, TransportErr> {
+        let req = self.callback_request(url, config, open_kfid)?;
+        ReqwestTransport::default().send(req).await
+    }
+}
+
+/// 运行中的HTTP模拟服务，drop时停止服务
+#[derive(Debug)]
+pub struct ServerGuard {
+    url: String,
+    task: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl ServerGuard {
+    /// 服务地址
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for ServerGuard {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(
+    State(server): State<MockServer>,
+    method: ::axum::http::Method,
+    uri: Uri,
+    body: Bytes,
+) -> Json<Value> {
+    let method = if method == ::axum::http::Method::GET {
+        Method::Get
+    } else {
+        Method::Post
+    };
+    Json(server.respond(method, &uri.to_string(), &body).await)
+}
+
+impl HttpTransport for MockServer {
+    fn send(&self, req: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportErr>> {
+        Box::pin(async move {
+            let res = self.respond(req.method, &req.url, &req.body).await;
+            Ok(HttpResponse::ok(res.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{Account, Page};
+    use crate::recall::RecallRequest;
+    use crate::receive::{MoreMsg, SyncMsg};
+    use crate::retry::RetryPolicy;
+    use crate::send::OutgoingMessage;
+    use crate::verify::WeiXinCallbackParam;
+    use crate::welcome::Welcome;
+    use crate::{Client, ClientErr, GetToken};
+
+    #[tokio::test]
+    async fn test_in_process() {
+        let server = MockServer::default();
+        let client = Client::with_transport("TOKEN", server.clone());
+        let res = client
+            .call(&GetToken::new("CORP_ID", "SECRET"))
+            .await
+            .unwrap();
+        let client = Client::with_transport(res.access_token.expose(), server.clone())
+            .retry(RetryPolicy::none())
+            .duplicate_msgid_errcode(DUPLICATE_MSGID);
+
+        let open_kfid = client
+            .call(&Account {
+                name: "客服".to_string(),
+                media_id: "MEDIA_ID".to_string(),
+            })
+            .await
+            .unwrap()
+            .open_kfid;
+        let res = client
+            .call(&Page {
+                offset: 0,
+                limit: 10,
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.account_list[0].open_kfid, open_kfid);
+
+        server.push_text(&open_kfid, "EXTERNAL_USERID", "1");
+        server.push_text(&open_kfid, "EXTERNAL_USERID", "2");
+        let code = server.enter_session(&open_kfid, "EXTERNAL_USERID", "SCENE");
+        let mut msg = SyncMsg {
+            open_kfid: Some(open_kfid.clone()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let res = client.sync_msg(&msg).await.unwrap();
+        assert_eq!(res.msg_list.len(), 2);
+        assert_eq!(res.has_more, MoreMsg::Yes);
+        msg.cursor = Some(res.next_cursor);
+        let res = client.sync_msg(&msg).await.unwrap();
+        assert_eq!(res.msg_list.len(), 1);
+        assert_eq!(res.has_more, MoreMsg::No);
+
+        let res = client
+            .reply(
+                &res.msg_list[0],
+                crate::send::MsgType::Text("hi".to_string()),
+            )
+            .await;
+        assert!(matches!(res, Err(ClientErr::Reply(_))));
+        let res = client
+            .send_welcome(&Welcome::text(&code, "欢迎"))
+            .await
+            .unwrap();
+        assert_eq!(res.errcode, 0);
+        let res = client
+            .send_welcome(&Welcome::text(&code, "欢迎"))
+            .await
+            .unwrap();
+        assert_eq!(res.errcode, INVALID_PARAMETER);
+
+        let message = OutgoingMessage::text("hi")
+            .to("EXTERNAL_USERID")
+            .from_kf(&open_kfid)
+            .msgid("MSG_ID")
+            .build()
+            .unwrap();
+        let res = client.send(&message).await.unwrap();
+        assert_eq!(res.msgid, "MSG_ID");
+        let res = client.send(&message).await.unwrap();
+        assert_eq!((res.errcode, res.msgid.as_str()), (0, "MSG_ID"));
+        assert_eq!(server.sent_messages().len(), 2);
+
+        let res = client
+            .recall(&RecallRequest::new(&open_kfid, "MSG_ID"))
+            .await
+            .unwrap();
+        assert_eq!(res.errcode, 0);
+        assert_eq!(server.recalled(), ["MSG_ID"]);
+
+        server.fail_next("/kf/account/list", -1);
+        let res = client
+            .call(&Page {
+                offset: 0,
+                limit: 10,
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.errcode, -1);
+
+        let res = Client::with_transport("INVALID", server)
+            .call(&Page {
+                offset: 0,
+                limit: 10,
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.errcode, INVALID_TOKEN);
+    }
+
+    #[tokio::test]
+    async fn test_http_server() {
+        let server = MockServer::default();
+        let guard = server.start().await.unwrap();
+        let url = guard.url().to_string();
+        let client = Client::new("TOKEN").base_url(&url);
+        let res = client
+            .call(&GetToken::new("CORP_ID", "SECRET"))
+            .await
+            .unwrap();
+        let client = Client::new(res.access_token.expose()).base_url(&url);
+        server.add_account("客服");
+        let res = client
+            .call(&Page {
+                offset: 0,
+                limit: 10,
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.account_list.len(), 1);
+
+        drop(guard);
+        tokio::task::yield_now().await;
+        let res = client
+            .call(&Page {
+                offset: 0,
+                limit: 10,
+            })
+            .await;
+        assert!(matches!(res, Err(ClientErr::Transport(_))));
+    }
+
+    #[test]
+    fn test_split_url() {
+        let (path, query) =
+            split_url("http://127.0.0.1:8080/cgi-bin/kf/sync_msg?access_token=A%2BB%3D&cursor=C+D");
+        assert_eq!(path, "/kf/sync_msg");
+        assert_eq!(query["access_token"], "A+B=");
+        assert_eq!(query["cursor"], "C D");
+    }
+
+    #[test]
+    fn test_callback_request() {
+        let server = MockServer::default();
+        let config = CallbackConfig::new(
+            "TOKEN",
+            "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C",
+            "CORP_ID",
+        );
+        let req = server
+            .callback_request("http://localhost/callback", &config, "OPEN_KFID")
+            .unwrap();
+        let (_, query) = split_url(&req.url);
+        let params = WeiXinCallbackParam {
+            timestamp: query["timestamp"].clone(),
+            nonce: query["nonce"].clone(),
+            echostr: String::new(),
+            msg_signature: query["msg_signature"].clone(),
+        };
+        let event = config
+            .decrypt(&params, std::str::from_utf8(&req.body).unwrap())
+            .unwrap();
+        assert_eq!(event.open_kf_id, "OPEN_KFID");
+        assert_eq!(event.to_user_name, "CORP_ID");
+
+        let state = server.state.lock().unwrap();
+        let sync = |open_kfid: &str, token: &str| {
+            state.sync_msg(&json!({ "open_kfid": open_kfid, "token": token }))["errcode"].clone()
+        };
+        assert_eq!(sync("OPEN_KFID", &event.token), 0);
+        assert_eq!(sync("OPEN_KFID", "MOCK_SYNC_TOKEN_0"), INVALID_MSG_TOKEN);
+        assert_eq!(sync("OTHER_KFID", &event.token), INVALID_MSG_TOKEN);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Deserialize;
+
+use crate::endpoint::{with_query, Endpoint};
+use crate::secret::Secret;
+
+#[derive(Debug, Deserialize)]
+pub struct AccessTokenRes {
+    pub errcode: i32,
+    pub errmsg: String,
+    #[serde(default)]
+    pub access_token: Secret<String>,
+    #[serde(default)]
+    pub expires_in: i32,
+}
+
+/// 获取access_token的接口路径
+pub(crate) const GET_TOKEN_PATH: &str = "/gettoken";
+
+/// 获取access_token的请求
+#[derive(Debug, Clone)]
+pub struct GetToken {
+    pub corpid: String,
+    pub corpsecret: Secret<String>,
+}
+
+impl GetToken {
+    pub fn new(id: &str, secret: &str) -> Self {
+        Self {
+            corpid: id.to_string(),
+            corpsecret: Secret::from(secret),
+        }
+    }
+}
+
+impl Endpoint for GetToken {
+    type Response = AccessTokenRes;
+
+    fn method(&self) -> crate::transport::Method {
+        crate::transport::Method::Get
+    }
+
+    fn path(&self) -> String {
+        with_query(
+            GET_TOKEN_PATH,
+            &[
+                ("corpid", &self.corpid),
+                ("corpsecret", self.corpsecret.expose()),
+            ],
+        )
+    }
+
+    fn body(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn authorized(&self) -> bool {
+        false
+    }
+}
+
+api! {
+    /// 获取access_token
+    fn access_token(id: &str, secret: &str) -> AccessTokenRes = GetToken::new(id, secret);
+}
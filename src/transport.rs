@@ -0,0 +1,207 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::secret::redact;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// 请求方法
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+/// 与HTTP客户端无关的请求
+#[derive(Clone, Eq, PartialEq)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl std::fmt::Debug for HttpRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpRequest")
+            .field("method", &self.method)
+            .field("url", &redact(&self.url))
+            .field("headers", &self.headers)
+            .field("body", &redact(&String::from_utf8_lossy(&self.body)))
+            .finish()
+    }
+}
+
+/// 与HTTP客户端无关的响应
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// 状态码为200的响应
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.into(),
+        }
+    }
+}
+
+/// 传输错误类型
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TransportErr {
+    /// 无法建立连接
+    Connect(String),
+    /// 请求超时
+    Timeout(String),
+    Other(String),
+}
+
+impl std::fmt::Display for TransportErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportErr::Connect(message) => write!(f, "connection failed: {}", redact(message)),
+            TransportErr::Timeout(message) => write!(f, "request timed out: {}", redact(message)),
+            TransportErr::Other(message) => write!(f, "transport error: {}", redact(message)),
+        }
+    }
+}
+
+impl std::error::Error for TransportErr {}
+
+impl TransportErr {
+    /// 隐去错误信息中的access_token等敏感参数
+    pub(crate) fn redacted(self) -> Self {
+        match self {
+            TransportErr::Connect(message) => TransportErr::Connect(redact(&message)),
+            TransportErr::Timeout(message) => TransportErr::Timeout(redact(&message)),
+            TransportErr::Other(message) => TransportErr::Other(redact(&message)),
+        }
+    }
+}
+
+/// HTTP传输层，负责发送请求并返回响应，可替换为自定义的HTTP客户端或测试用的实现
+pub trait HttpTransport: Send + Sync + 'static {
+    fn send(&self, req: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportErr>>;
+}
+
+/// 基于reqwest的默认传输层
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for TransportErr {
+    fn from(value: reqwest::Error) -> Self {
+        let (timeout, connect) = (value.is_timeout(), value.is_connect());
+        let message = redact(&value.without_url().to_string());
+        if timeout {
+            TransportErr::Timeout(message)
+        } else if connect {
+            TransportErr::Connect(message)
+        } else {
+            TransportErr::Other(message)
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, req: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportErr>> {
+        Box::pin(async move {
+            let res = crate::request::to_reqwest(&self.client, req).send().await?;
+            let status = res.status().as_u16();
+            let headers = res
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+                .collect();
+            let body = res.bytes().await?.to_vec();
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// 测试用的传输层
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// 记录请求并依次返回预设响应的传输层，预设响应用完后重复最后一个
+    #[derive(Default)]
+    pub(crate) struct MockTransport {
+        pub requests: Mutex<Vec<HttpRequest>>,
+        pub responses: Vec<String>,
+    }
+
+    impl HttpTransport for Arc<MockTransport> {
+        fn send(&self, req: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportErr>> {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(req);
+            let index = (requests.len() - 1).min(self.responses.len() - 1);
+            let res = HttpResponse::ok(self.responses[index].as_bytes());
+            Box::pin(async move { Ok(res) })
+        }
+    }
+
+    pub(crate) fn mock(response: &str) -> Arc<MockTransport> {
+        mock_seq(&[response])
+    }
+
+    pub(crate) fn mock_seq(responses: &[&str]) -> Arc<MockTransport> {
+        Arc::new(MockTransport {
+            responses: responses.iter().map(|res| res.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    /// 不关心接口调用的测试使用的客户端
+    pub(crate) fn client() -> crate::Client {
+        crate::Client::with_transport("TOKEN", mock(r#"{"errcode":0,"errmsg":"ok"}"#))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_redacted() {
+        let req = HttpRequest {
+            method: Method::Post,
+            url: "https://qyapi.weixin.qq.com/cgi-bin/kf/send_msg?access_token=TOKEN".to_string(),
+            headers: vec![],
+            body: br#"{"token":"SYNC_TOKEN"}"#.to_vec(),
+        };
+        let debug = format!("{req:?}");
+        assert!(debug.contains("access_token=***"));
+        assert!(!debug.contains("TOKEN"));
+    }
+
+    #[test]
+    fn test_transport_err_display() {
+        let err = TransportErr::Timeout("GET /kf/sync_msg?access_token=TOKEN".to_string());
+        assert_eq!(
+            err.to_string(),
+            "request timed out: GET /kf/sync_msg?access_token=***"
+        );
+    }
+}
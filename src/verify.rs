@@ -1,13 +1,27 @@
 use crate::decrypt::{decrypt_msg, DecryptErr};
-use crate::signature::{msg_signature, Signature};
+use crate::signature::{msg_signature, signature_eq, Signature};
 use serde::Deserialize;
 
-#[derive(Debug, Clone, Deserialize)]
+/// 回调请求的URL参数，验证URL时携带`echostr`，接收回调时不携带
+#[derive(Clone, Deserialize)]
 pub struct WeiXinCallbackParam {
-    timestamp: String,
-    nonce: String,
-    echostr: String,
-    msg_signature: String,
+    pub timestamp: String,
+    pub nonce: String,
+    #[serde(default)]
+    pub echostr: String,
+    pub msg_signature: String,
+}
+
+/// `Debug`输出时隐去签名和加密的echostr
+impl std::fmt::Debug for WeiXinCallbackParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeiXinCallbackParam")
+            .field("timestamp", &self.timestamp)
+            .field("nonce", &self.nonce)
+            .field("echostr", &"***")
+            .field("msg_signature", &"***")
+            .finish()
+    }
 }
 
 /// 验证错误类型
@@ -15,6 +29,20 @@ pub struct WeiXinCallbackParam {
 pub enum VerifyErr {
     Decrypt(DecryptErr),
     Signature,
+    /// 回调内容不是合法的XML
+    Xml(String),
+    /// 解密得到的receiveid与企业ID不一致，值为解密得到的receiveid
+    CorpId(String),
+}
+
+impl VerifyErr {
+    /// 回调请求校验失败时响应的HTTP状态码
+    pub fn status(&self) -> u16 {
+        match self {
+            VerifyErr::Signature | VerifyErr::CorpId(_) => 403,
+            VerifyErr::Decrypt(_) | VerifyErr::Xml(_) => 400,
+        }
+    }
 }
 
 impl From<DecryptErr> for VerifyErr {
@@ -22,24 +50,72 @@ impl From<DecryptErr> for VerifyErr {
         VerifyErr::Decrypt(value)
     }
 }
+
+/// 校验签名
+pub(crate) fn check_signature(
+    callback_params: &WeiXinCallbackParam,
+    token: &str,
+    encrypt: &str,
+) -> Result<(), VerifyErr> {
+    let signature = Signature::new(
+        token,
+        &callback_params.timestamp,
+        &callback_params.nonce,
+        encrypt,
+    );
+    if signature_eq(&msg_signature(&signature), &callback_params.msg_signature) {
+        Ok(())
+    } else {
+        Err(VerifyErr::Signature)
+    }
+}
+
 /// 验证URL有效性
 pub fn verify_url(
     callback_params: &WeiXinCallbackParam,
     token: &str,
     encoding_ase_key: &str,
 ) -> Result<String, VerifyErr> {
-    let WeiXinCallbackParam {
-        timestamp,
-        nonce,
-        echostr,
-        msg_signature: old_signature,
-    } = callback_params.clone();
-    let signature = Signature::new(token, &timestamp, &nonce, &echostr);
-    let new_signature = msg_signature(&signature);
-    if old_signature == new_signature {
-        Err(VerifyErr::Signature)
-    } else {
-        let msg = decrypt_msg(&echostr, encoding_ase_key)?.msg;
-        Ok(msg)
+    check_signature(callback_params, token, &callback_params.echostr)?;
+    let msg = decrypt_msg(&callback_params.echostr, encoding_ase_key)?.msg;
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_url() {
+        let echostr = "RypEvHKD8QQKFhvQ6QleEB4J58tiPdvo+rtK1I9qca6aM/wvqnLSV5zEPeusUiX5L5X/0lWfrf0QADHHhGd3QczcdCUpj911L3vg3W/sYYvuJTs3TUUkSUXxaccAS0qhxchrRYt66wiSpGLYL42aM6A8dTT+6k4aSknmPj48kzJs8qLjvd4Xgpue06DOdnLxAUHzM6+kDZ+HMZfJYuR+LtwGc2hgf5gsijff0ekUNXZiqATP7PF5mZxZ3Izoun1s4zG4LUMnvw2r+KqCKIw+3IQH03v+BCA9nMELNqbSf6tiWSrXJB3LAVGUcallcrw8V2t9EL4EhzJWrQUax5wLVMNS0+rUPA3k22Ncx4XXZS9o0MBH27Bo6BpNelZpS+/uh9KsNlY6bHCmJU9p8g7m3fVKn28H3KDYA5Pl/T8Z1ptDAVe0lXdQ2YoyyH2uyPIGHBZZIs2pDBS8R07+qN+E7Q==";
+        let mut params = WeiXinCallbackParam {
+            timestamp: "1409659813".to_string(),
+            nonce: "1372623149".to_string(),
+            echostr: echostr.to_string(),
+            msg_signature: "477715d11cdb4164915debcba66cb864d751f3e6".to_string(),
+        };
+        let encoding_ase_key = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+        let result = verify_url(&params, "QDG6eK", encoding_ase_key).unwrap();
+        assert!(result.starts_with("<xml><ToUserName><![CDATA[wx5823bf96d3bd56c7]]>"));
+        let debug = format!("{params:?}");
+        assert!(!debug.contains("477715d1") && !debug.contains("RypEvHKD"));
+
+        params.msg_signature = "0".repeat(40);
+        let result = verify_url(&params, "QDG6eK", encoding_ase_key);
+        assert_eq!(result, Err(VerifyErr::Signature));
+    }
+
+    /// 签名正确但echostr被篡改时也必须拒绝，之前的实现把签名比较的结果弄反了
+    #[test]
+    fn test_verify_url_tampered() {
+        let params = WeiXinCallbackParam {
+            timestamp: "1409659813".to_string(),
+            nonce: "1372623149".to_string(),
+            echostr: "AAAA".to_string(),
+            msg_signature: "477715d11cdb4164915debcba66cb864d751f3e6".to_string(),
+        };
+        let encoding_ase_key = "jWmYm7qr5nMoAUwZRjGtBxmz3KA1tkAj3ykkR6q2B2C";
+        let result = verify_url(&params, "QDG6eK", encoding_ase_key);
+        assert_eq!(result, Err(VerifyErr::Signature));
     }
 }
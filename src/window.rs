@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dispatcher::{BoxFuture, Context, Flow};
+use crate::middleware::{Middleware, Next};
+use crate::msg_res::{MsgItem, MsgOrigin};
+
+/// 客户发送消息后可向其发送消息的时间窗口（秒）
+pub const SEND_WINDOW_SECS: u64 = 48 * 60 * 60;
+
+/// 时间窗口内最多可发送的消息条数
+pub const MAX_MESSAGES_PER_WINDOW: u32 = 5;
+
+/// 客户的发送窗口状态
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct WindowState {
+    /// 客户最后一条消息的发送时间
+    pub last_customer_msg: u64,
+    /// 该消息之后已发送的消息条数
+    pub sent: u32,
+}
+
+/// 发送窗口状态的存储，可替换为持久化存储以便多实例共享
+///
+/// 占用和归还额度都通过[`WindowStore::compare_and_swap`]完成，多实例共享时该操作必须是原子的，
+/// 如Redis中用Lua脚本或`WATCH`/`MULTI`实现。
+pub trait WindowStore: Send + Sync + 'static {
+    fn get(&self, open_kfid: &str, external_userid: &str) -> Option<WindowState>;
+
+    /// 当前状态等于`current`时写入`new`并返回`true`，否则不修改并返回`false`
+    fn compare_and_swap(
+        &self,
+        open_kfid: &str,
+        external_userid: &str,
+        current: Option<WindowState>,
+        new: WindowState,
+    ) -> bool;
+}
+
+/// 内存中的发送窗口存储
+#[derive(Debug, Default)]
+pub struct MemoryWindowStore {
+    states: Mutex<HashMap<(String, String), WindowState>>,
+}
+
+impl WindowStore for MemoryWindowStore {
+    fn get(&self, open_kfid: &str, external_userid: &str) -> Option<WindowState> {
+        let key = (open_kfid.to_string(), external_userid.to_string());
+        self.states.lock().unwrap().get(&key).copied()
+    }
+
+    fn compare_and_swap(
+        &self,
+        open_kfid: &str,
+        external_userid: &str,
+        current: Option<WindowState>,
+        new: WindowState,
+    ) -> bool {
+        let key = (open_kfid.to_string(), external_userid.to_string());
+        let mut states = self.states.lock().unwrap();
+        if states.get(&key).copied() != current {
+            return false;
+        }
+        states.insert(key, new);
+        true
+    }
+}
+
+/// 剩余的发送额度
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Quota {
+    /// 还可发送的消息条数
+    pub remaining: u32,
+    /// 窗口结束的时间戳，同时标识额度所属的窗口
+    pub expires_at: u64,
+}
+
+/// 发送窗口错误类型
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum WindowErr {
+    /// 没有客户发送过消息的记录
+    NoCustomerMessage,
+    /// 窗口已于该时间戳结束
+    Expired(u64),
+    /// 窗口内的消息条数已用完，窗口于该时间戳结束
+    Exhausted(u64),
+}
+
+impl std::fmt::Display for WindowErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowErr::NoCustomerMessage => write!(f, "the customer has not sent any message"),
+            WindowErr::Expired(expires_at) => write!(f, "the send window ended at {expires_at}"),
+            WindowErr::Exhausted(expires_at) => write!(
+                f,
+                "the send window allows no more messages until it ends at {expires_at}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WindowErr {}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 跟踪每个客户的发送窗口：客户发送消息后48小时内最多可向其发送5条消息
+///
+/// 通过[`WindowTracker::record_inbound`]或作为中间件记录客户发送的消息，
+/// 设置到[`Client::send_window`](crate::Client::send_window)后发送消息前会在本地校验额度。
+#[derive(Clone)]
+pub struct WindowTracker {
+    store: Arc<dyn WindowStore>,
+}
+
+impl WindowTracker {
+    pub fn new(store: Arc<dyn WindowStore>) -> Self {
+        Self { store }
+    }
+
+    /// 记录收到的消息，只有微信客户发送的消息会开启新的窗口
+    pub fn record_inbound(&self, item: &MsgItem) {
+        if item.origin != MsgOrigin::WeiXinCustomer {
+            return;
+        }
+        let (Some(open_kfid), Some(external_userid)) = (&item.open_kfid, &item.external_userid)
+        else {
+            return;
+        };
+        let new = WindowState {
+            last_customer_msg: item.send_time,
+            sent: 0,
+        };
+        loop {
+            let state = self.store.get(open_kfid, external_userid);
+            if state.is_some_and(|state| state.last_customer_msg >= item.send_time) {
+                return;
+            }
+            if self
+                .store
+                .compare_and_swap(open_kfid, external_userid, state, new)
+            {
+                return;
+            }
+        }
+    }
+
+    /// 查询当前的发送额度
+    pub fn can_send(&self, open_kfid: &str, external_userid: &str) -> Result<Quota, WindowErr> {
+        self.quota_at(self.store.get(open_kfid, external_userid), now())
+    }
+
+    fn quota_at(&self, state: Option<WindowState>, now: u64) -> Result<Quota, WindowErr> {
+        let state = state.ok_or(WindowErr::NoCustomerMessage)?;
+        let expires_at = state.last_customer_msg + SEND_WINDOW_SECS;
+        if now >= expires_at {
+            return Err(WindowErr::Expired(expires_at));
+        }
+        if state.sent >= MAX_MESSAGES_PER_WINDOW {
+            return Err(WindowErr::Exhausted(expires_at));
+        }
+        Ok(Quota {
+            remaining: MAX_MESSAGES_PER_WINDOW - state.sent,
+            expires_at,
+        })
+    }
+
+    /// 为一次发送占用额度，返回占用后剩余的额度
+    ///
+    /// 发送失败时需用返回的额度调用[`WindowTracker::release`]归还。
+    pub fn reserve(&self, open_kfid: &str, external_userid: &str) -> Result<Quota, WindowErr> {
+        self.reserve_at(open_kfid, external_userid, now())
+    }
+
+    fn reserve_at(
+        &self,
+        open_kfid: &str,
+        external_userid: &str,
+        now: u64,
+    ) -> Result<Quota, WindowErr> {
+        loop {
+            let state = self.store.get(open_kfid, external_userid);
+            let quota = self.quota_at(state, now)?;
+            let Some(current) = state else {
+                unreachable!("quota_at rejects missing state")
+            };
+            let new = WindowState {
+                sent: current.sent + 1,
+                ..current
+            };
+            if self
+                .store
+                .compare_and_swap(open_kfid, external_userid, state, new)
+            {
+                return Ok(Quota {
+                    remaining: quota.remaining - 1,
+                    ..quota
+                });
+            }
+        }
+    }
+
+    /// 归还发送失败的消息占用的额度，`quota`为[`WindowTracker::reserve`]的返回值
+    ///
+    /// 客户在此期间发送了新消息而开启了新窗口时不做任何修改，新窗口的额度不受影响。
+    pub fn release(&self, open_kfid: &str, external_userid: &str, quota: &Quota) {
+        loop {
+            let Some(current) = self.store.get(open_kfid, external_userid) else {
+                return;
+            };
+            if current.last_customer_msg + SEND_WINDOW_SECS != quota.expires_at {
+                return;
+            }
+            let new = WindowState {
+                sent: current.sent.saturating_sub(1),
+                ..current
+            };
+            if self
+                .store
+                .compare_and_swap(open_kfid, external_userid, Some(current), new)
+            {
+                return;
+            }
+        }
+    }
+}
+
+impl Middleware for WindowTracker {
+    fn handle<'a>(&'a self, ctx: Context, next: Next<'a>) -> BoxFuture<'a, Flow> {
+        self.record_inbound(&ctx.item);
+        next.run(ctx)
+    }
+}
+
+impl std::fmt::Debug for WindowTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WindowTracker").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    fn customer_item(send_time: u64) -> MsgItem {
+        let str = format!(
+            r#"{{
+                "msgid": "MSG_ID",
+                "open_kfid": "OPEN_KFID",
+                "external_userid": "EXTERNAL_USERID",
+                "send_time": {send_time},
+                "origin": 3,
+                "msgtype": "text",
+                "text": {{ "content": "hello" }}
+            }}"#
+        );
+        from_str(&str).unwrap()
+    }
+
+    #[test]
+    fn test_window() {
+        let tracker = WindowTracker::new(Arc::new(MemoryWindowStore::default()));
+        let start = 1_700_000_000;
+        assert_eq!(
+            tracker.reserve_at("OPEN_KFID", "EXTERNAL_USERID", start),
+            Err(WindowErr::NoCustomerMessage)
+        );
+
+        tracker.record_inbound(&customer_item(start));
+        let expires_at = start + SEND_WINDOW_SECS;
+        for remaining in (0..MAX_MESSAGES_PER_WINDOW).rev() {
+            let quota = tracker.reserve_at("OPEN_KFID", "EXTERNAL_USERID", start + 1);
+            assert_eq!(
+                quota,
+                Ok(Quota {
+                    remaining,
+                    expires_at
+                })
+            );
+        }
+        assert_eq!(
+            tracker.reserve_at("OPEN_KFID", "EXTERNAL_USERID", start + 1),
+            Err(WindowErr::Exhausted(expires_at))
+        );
+
+        let quota = Quota {
+            remaining: 0,
+            expires_at,
+        };
+        tracker.release("OPEN_KFID", "EXTERNAL_USERID", &quota);
+        assert_eq!(
+            tracker.reserve_at("OPEN_KFID", "EXTERNAL_USERID", start + 1),
+            Ok(quota)
+        );
+        assert_eq!(
+            tracker.reserve_at("OPEN_KFID", "EXTERNAL_USERID", expires_at),
+            Err(WindowErr::Expired(expires_at))
+        );
+    }
+
+    #[test]
+    fn test_new_customer_message_resets_window() {
+        let tracker = WindowTracker::new(Arc::new(MemoryWindowStore::default()));
+        tracker.record_inbound(&customer_item(100));
+        tracker
+            .reserve_at("OPEN_KFID", "EXTERNAL_USERID", 101)
+            .unwrap();
+
+        // 乱序到达的旧消息不影响窗口
+        tracker.record_inbound(&customer_item(50));
+        let old = tracker
+            .reserve_at("OPEN_KFID", "EXTERNAL_USERID", 101)
+            .unwrap();
+        assert_eq!(old.remaining, MAX_MESSAGES_PER_WINDOW - 2);
+
+        tracker.record_inbound(&customer_item(200));
+        let quota = tracker
+            .reserve_at("OPEN_KFID", "EXTERNAL_USERID", 201)
+            .unwrap();
+        assert_eq!(quota.remaining, MAX_MESSAGES_PER_WINDOW - 1);
+        assert_eq!(quota.expires_at, 200 + SEND_WINDOW_SECS);
+
+        // 旧窗口的占用不能归还到新窗口
+        tracker.release("OPEN_KFID", "EXTERNAL_USERID", &old);
+        let state = tracker.store.get("OPEN_KFID", "EXTERNAL_USERID").unwrap();
+        assert_eq!(state.sent, 1);
+        tracker.release("OPEN_KFID", "EXTERNAL_USERID", &quota);
+        let state = tracker.store.get("OPEN_KFID", "EXTERNAL_USERID").unwrap();
+        assert_eq!(state.sent, 0);
+    }
+
+    /// 模拟另一个实例在读取和写入之间修改了状态
+    struct RacingStore {
+        inner: MemoryWindowStore,
+        races: Mutex<u32>,
+    }
+
+    impl WindowStore for RacingStore {
+        fn get(&self, open_kfid: &str, external_userid: &str) -> Option<WindowState> {
+            self.inner.get(open_kfid, external_userid)
+        }
+
+        fn compare_and_swap(
+            &self,
+            open_kfid: &str,
+            external_userid: &str,
+            current: Option<WindowState>,
+            new: WindowState,
+        ) -> bool {
+            let mut races = self.races.lock().unwrap();
+            if *races > 0 {
+                *races -= 1;
+                let mut other = current.unwrap();
+                other.sent += 1;
+                assert!(self
+                    .inner
+                    .compare_and_swap(open_kfid, external_userid, current, other));
+            }
+            self.inner
+                .compare_and_swap(open_kfid, external_userid, current, new)
+        }
+    }
+
+    #[test]
+    fn test_shared_store_race() {
+        let store = Arc::new(RacingStore {
+            inner: MemoryWindowStore::default(),
+            races: Mutex::new(0),
+        });
+        let tracker = WindowTracker::new(store.clone());
+        tracker.record_inbound(&customer_item(100));
+        for _ in 0..MAX_MESSAGES_PER_WINDOW - 1 {
+            tracker
+                .reserve_at("OPEN_KFID", "EXTERNAL_USERID", 101)
+                .unwrap();
+        }
+        // 另一个实例抢先占用了最后一条额度
+        *store.races.lock().unwrap() = 1;
+        assert_eq!(
+            tracker.reserve_at("OPEN_KFID", "EXTERNAL_USERID", 101),
+            Err(WindowErr::Exhausted(100 + SEND_WINDOW_SECS))
+        );
+        let state = store.get("OPEN_KFID", "EXTERNAL_USERID").unwrap();
+        assert_eq!(state.sent, MAX_MESSAGES_PER_WINDOW);
+    }
+}